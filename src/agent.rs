@@ -2,37 +2,99 @@ use std::time::Instant;
 
 use rand::prelude::*;
 
+use super::desync;
+use super::diagnostic;
 use super::inputs::*;
-use super::movement;
+use super::movement::{self,AssignmentsPerPlayer};
 use super::view::*;
 use super::planning::{self,*};
-use super::solving::{Candidate,Solver,SolverSession};
+use super::solving::{Candidate,Solver,SolverConfig,SolverSession};
 use super::valuation::SpawnEvaluator;
 
+const SEED: u64 = 0x1234567890abcdef;
 const ADVERSARY_MS: u128 = 10;
 const SEARCH_MS: u128 = 80;
 
+#[derive(Clone,Debug)]
+pub struct AgentConfig {
+    pub seed: u64,
+
+    /// Time spent each tick optimizing the enemy's plan
+    pub adversary_ms: u128,
+
+    /// Time spent each tick optimizing our plan, after the enemy's
+    pub search_ms: u128,
+
+    /// If set, each tick takes exactly this many solver steps for each player instead of using the time budgets, so that runs are reproducible
+    pub iterations: Option<u32>,
+
+    pub solver: SolverConfig,
+
+    /// Follow each desync with the simulated events that led to it. Too verbose for live games, where the enemy's assignments are only guesses.
+    pub explain_desyncs: bool,
+
+    /// Emit chains of beacons as LINE commands. Off by default because the referee may break ties between shortest paths differently.
+    pub use_lines: bool,
+}
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            seed: SEED,
+            adversary_ms: ADVERSARY_MS,
+            search_ms: SEARCH_MS,
+            iterations: None,
+            solver: SolverConfig::default(),
+            explain_desyncs: false,
+            use_lines: false,
+        }
+    }
+}
+
 pub struct Agent {
+    config: AgentConfig,
     solvers: [Solver; NUM_PLAYERS],
     plans: [Vec<Milestone>; NUM_PLAYERS],
     rng: StdRng,
+
+    /// The last state seen and the assignments we expected both players to make from it
+    previous: Option<(State,AssignmentsPerPlayer)>,
 }
 impl Agent {
+    #[allow(dead_code)]
     pub fn new(view: &View) -> Self {
+        Self::with_config(view, AgentConfig::default())
+    }
+
+    pub fn with_config(view: &View, config: AgentConfig) -> Self {
         Self {
             solvers: [
-                Solver::new(ME, view),
-                Solver::new(ENEMY, view),
+                Solver::new(ME, view, config.solver.clone()),
+                Solver::new(ENEMY, view, config.solver.clone()),
             ],
             plans: [Vec::new(), Vec::new()],
-            rng: StdRng::seed_from_u64(0x1234567890abcdef),
+            rng: StdRng::seed_from_u64(config.seed),
+            previous: None,
+            config,
         }
     }
 
     pub fn act(&mut self, view: &View, state: &State) -> Vec<Action> {
         let start = Instant::now();
-        eprintln!("Crystals: me={}, enemy={}", state.crystals[0], state.crystals[1]);
-        eprintln!("Ants: me={}, enemy={}", state.total_ants[0], state.total_ants[1]);
+        diagnostic!("Crystals: me={}, enemy={}", state.crystals[0], state.crystals[1]);
+        diagnostic!("Ants: me={}, enemy={}", state.total_ants[0], state.total_ants[1]);
+
+        if let Some((previous, assignments)) = self.previous.take() {
+            if previous.tick + 1 == state.tick {
+                let desync = desync::detect(&previous, &assignments, view, state);
+                if !desync.is_empty() {
+                    if self.config.explain_desyncs {
+                        diagnostic!("{:#}", desync);
+                    } else {
+                        diagnostic!("{}", desync);
+                    }
+                }
+            }
+        }
 
         for plan in self.plans.iter_mut() {
             Milestone::reap(plan, state);
@@ -40,16 +102,20 @@ impl Agent {
 
         let mut enemy_session = SolverSession::new(Candidate::evaluate(ENEMY, self.plans[ENEMY].clone(), &self.plans[ME], view, state));
         let initial_adversarial_score = -enemy_session.best.score;
-        while start.elapsed().as_millis() < ADVERSARY_MS {
+        let mut num_steps = 0;
+        while self.has_budget(num_steps, &start, self.config.adversary_ms) {
             self.solvers[ENEMY].step(&mut enemy_session, &self.plans[ME], view, state, &mut self.rng);
+            num_steps += 1;
         }
         self.plans[ENEMY] = enemy_session.best.plan.clone();
 
         let mut my_session = SolverSession::new(Candidate::evaluate(ME, self.plans[ME].clone(), &self.plans[ENEMY], view, state));
         let initial_score = my_session.best.score;
-        eprintln!("Initial: {}", my_session.best);
-        while start.elapsed().as_millis() < SEARCH_MS + ADVERSARY_MS {
+        diagnostic!("Initial: {}", my_session.best);
+        let mut num_steps = 0;
+        while self.has_budget(num_steps, &start, self.config.search_ms + self.config.adversary_ms) {
             self.solvers[ME].step(&mut my_session, &self.plans[ENEMY], view, state, &mut self.rng);
+            num_steps += 1;
         }
         self.plans[ME] = my_session.best.plan.clone();
 
@@ -58,11 +124,11 @@ impl Agent {
         let stats = [my_session.stats, enemy_session.stats];
 
         let num_evaluated = stats.iter().map(|s| s.num_evaluated()).sum::<i32>();
-        eprintln!("{:.0} -> {:.0} -> {:.0} -> found best plan in {:.0} ms ({} iterations)",
+        diagnostic!("{:.0} -> {:.0} -> {:.0} -> found best plan in {:.0} ms ({} iterations)",
             initial_adversarial_score, initial_score, best.score,
             start.elapsed().as_millis() as f32,
             num_evaluated);
-        eprintln!("Successful: {}/{} generations, {}/{} mutations",
+        diagnostic!("Successful: {}/{} generations, {}/{} mutations",
             stats.iter().map(|s| s.num_successful_generations()).sum::<i32>(),
             stats.iter().map(|s| s.num_generations()).sum::<i32>(),
             stats.iter().map(|s| s.num_successful_mutations()).sum::<i32>(),
@@ -76,22 +142,33 @@ impl Agent {
         let commands = planning::enact_plan(ME, &best.plan, view, state);
         let countermoves = planning::enact_plan(ENEMY, &adversary.plan, view, state);
 
-        let mut actions = movement::assignments_to_actions(&commands.assignments);
+        let mut actions = movement::assignments_to_actions(&commands.assignments, self.config.use_lines, &state.num_ants[ME], view);
         actions.push(Action::Message { text: format!("{}", num_evaluated) });
 
-        eprintln!("Best: {}", best);
-        eprintln!(
-            "Endgame: tick={}, crystals=[{} vs {}], ants=[{} vs {}]",
+        self.previous = Some((state.clone(), [commands.assignments.clone(), countermoves.assignments.clone()]));
+
+        diagnostic!("Best: {}", best);
+        diagnostic!(
+            "Endgame: tick={}, crystals=[{} vs {}], ants=[{} vs {}], outcome={:?}",
             best.endgame.tick,
             best.endgame.crystals[0], best.endgame.crystals[1],
             best.endgame.total_ants[0], best.endgame.total_ants[1],
+            best.endgame.outcome,
         );
-        eprintln!("Goals: {} vs {}", commands, countermoves);
-        eprintln!("Ticks to win: {:.0} vs {:.0}", harvests[0].ticks_to_harvest_remaining_crystals(), harvests[1].ticks_to_harvest_remaining_crystals());
-        eprintln!("Ticks saved from 1 egg: {:.2} vs {:.2}", harvests[0].calculate_ticks_saved_harvesting_eggs(1), harvests[1].calculate_ticks_saved_harvesting_eggs(1));
+        diagnostic!("Goals: {} vs {}", commands, countermoves);
+        diagnostic!("Ticks to win: {:.0} vs {:.0}", harvests[0].ticks_to_harvest_remaining_crystals(), harvests[1].ticks_to_harvest_remaining_crystals());
+        diagnostic!("Ticks saved from 1 egg: {:.2} vs {:.2}", harvests[0].calculate_ticks_saved_harvesting_eggs(1), harvests[1].calculate_ticks_saved_harvesting_eggs(1));
 
-        eprintln!("{}", self.solvers[ME]);
+        diagnostic!("{}", self.solvers[ME]);
 
         actions
     }
-}
\ No newline at end of file
+
+    /// Whether to keep searching after num_steps, given the milliseconds since the start of the tick that the search may run until
+    fn has_budget(&self, num_steps: u32, start: &Instant, until_ms: u128) -> bool {
+        match self.config.iterations {
+            Some(iterations) => num_steps < iterations,
+            None => start.elapsed().as_millis() < until_ms,
+        }
+    }
+}
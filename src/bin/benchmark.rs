@@ -0,0 +1,74 @@
+use std::time::{Duration,Instant};
+
+use rand::prelude::*;
+
+use spring_challenge_2023::evaluation;
+use spring_challenge_2023::inputs::*;
+use spring_challenge_2023::mapgen;
+use spring_challenge_2023::planning::Milestone;
+use spring_challenge_2023::view::*;
+
+const USAGE: &str = "Usage: benchmark [--maps <maps to generate>] [--largest <maps to benchmark>] [--iterations <n>]";
+
+fn main() {
+    let mut num_maps = 100;
+    let mut num_largest = 5;
+    let mut num_iterations = 100;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| exit_with_usage());
+        match arg.as_str() {
+            "--maps" => num_maps = value.parse().unwrap_or_else(|_| exit_with_usage()),
+            "--largest" => num_largest = value.parse().unwrap_or_else(|_| exit_with_usage()),
+            "--iterations" => num_iterations = value.parse().unwrap_or_else(|_| exit_with_usage()),
+            _ => exit_with_usage(),
+        }
+    }
+    if num_iterations == 0 { exit_with_usage() }
+
+    // The largest maps are the slowest, so they are the ones that matter for the time limit
+    let mut maps: Vec<(u64,Layout,State)> = (0..num_maps).map(|seed| {
+        let (layout, state) = mapgen::generate(&mut StdRng::seed_from_u64(seed));
+        (seed, layout, state)
+    }).collect();
+    maps.sort_by_key(|(seed, layout, _)| (std::cmp::Reverse(layout.cells.len()), *seed));
+    maps.truncate(num_largest);
+
+    let mut total_view = Duration::ZERO;
+    let mut total_rollout = Duration::ZERO;
+    for (seed, layout, state) in maps.iter() {
+        let start = Instant::now();
+        for _ in 0..num_iterations {
+            std::hint::black_box(View::new(layout.clone()));
+        }
+        let view_elapsed = start.elapsed() / num_iterations;
+
+        let view = View::new(layout.clone());
+        let plans = [
+            view.closest_resources[ME].iter().map(|&cell| Milestone::Harvest(cell)).collect::<Vec<_>>(),
+            view.closest_resources[ENEMY].iter().map(|&cell| Milestone::Harvest(cell)).collect::<Vec<_>>(),
+        ];
+        let start = Instant::now();
+        for _ in 0..num_iterations {
+            std::hint::black_box(evaluation::rollout([&plans[ME], &plans[ENEMY]], &view, state));
+        }
+        let rollout_elapsed = start.elapsed() / num_iterations;
+
+        println!("Map {}: {} cells, View::new {:.1} µs, rollout {:.1} µs", seed, layout.cells.len(), micros(view_elapsed), micros(rollout_elapsed));
+        total_view += view_elapsed;
+        total_rollout += rollout_elapsed;
+    }
+
+    let num_benchmarked = maps.len().max(1) as u32;
+    println!("Average: View::new {:.1} µs, rollout {:.1} µs", micros(total_view / num_benchmarked), micros(total_rollout / num_benchmarked));
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1e6
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
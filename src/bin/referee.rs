@@ -0,0 +1,181 @@
+use std::fs::File;
+use std::io::{BufReader,BufWriter,Write};
+use std::process::{Child,ChildStdin,Command,Stdio};
+use std::sync::mpsc::{self,Receiver,RecvTimeoutError};
+use std::time::{Duration,Instant};
+
+use rand::prelude::*;
+
+use spring_challenge_2023::inputs::*;
+use spring_challenge_2023::interface::{self,ProtocolError,ProtocolReader,TurnInput};
+use spring_challenge_2023::mapgen;
+use spring_challenge_2023::movement::{self,AssignmentsPerPlayer,MovementModel};
+use spring_challenge_2023::simulation;
+use spring_challenge_2023::view::{self,*};
+
+const USAGE: &str = "Usage: referee (--map <map file> | --seed <map seed>) [--timeout <ms per turn>] [--verbose] <bot 1 command> <bot 2 command>";
+
+/// Longer than the contest allows because both bots share this machine
+const TURN_TIMEOUT_MS: u64 = 1000;
+
+struct Bot {
+    process: Child,
+    input: BufWriter<ChildStdin>,
+
+    /// Each turn's actions, read on a separate thread so that a bot that stops responding cannot hang the referee
+    actions: Receiver<Result<Vec<Action>,ProtocolError>>,
+}
+impl Bot {
+    fn spawn(command: &str, layout: &Layout, verbose: bool) -> Self {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().unwrap_or_else(|| exit_with_usage());
+        let mut process = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(if verbose { Stdio::inherit() } else { Stdio::null() })
+            .spawn()
+            .unwrap_or_else(|error| {
+                eprintln!("Unable to start {}: {}", command, error);
+                std::process::exit(1);
+            });
+
+        let input = BufWriter::new(process.stdin.take().expect("missing stdin"));
+        let mut output = ProtocolReader::new(BufReader::new(process.stdout.take().expect("missing stdout")));
+
+        let layout = layout.clone();
+        let (sender, actions) = mpsc::channel();
+        std::thread::spawn(move || loop {
+            let result = interface::read_actions(&mut output, &layout);
+            let failed = result.is_err();
+            if sender.send(result).is_err() || failed { break }
+        });
+
+        Self { process, input, actions }
+    }
+
+    /// The bot's next actions, or why it failed to give them before the deadline
+    fn receive(&self, deadline: Instant) -> Result<Vec<Action>,String> {
+        match self.actions.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(result) => result.map_err(|error| error.to_string()),
+            Err(RecvTimeoutError::Timeout) => Err("timed out".to_string()),
+            Err(RecvTimeoutError::Disconnected) => Err("stopped responding".to_string()),
+        }
+    }
+
+    fn send(&mut self, write: impl FnOnce(&mut BufWriter<ChildStdin>) -> std::io::Result<()>) {
+        // If the bot has died, writing fails but we will find out when reading its response
+        let _ = write(&mut self.input).and_then(|_| self.input.flush());
+    }
+}
+impl Drop for Bot {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+fn main() {
+    let mut map_path = None;
+    let mut seed = None;
+    let mut timeout = Duration::from_millis(TURN_TIMEOUT_MS);
+    let mut verbose = false;
+    let mut commands = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--map" => map_path = Some(args.next().unwrap_or_else(|| exit_with_usage())),
+            "--seed" => seed = Some(args.next().and_then(|seed| seed.parse::<u64>().ok()).unwrap_or_else(|| exit_with_usage())),
+            "--timeout" => timeout = Duration::from_millis(args.next().and_then(|ms| ms.parse::<u64>().ok()).unwrap_or_else(|| exit_with_usage())),
+            "--verbose" => verbose = true,
+            _ => commands.push(arg),
+        }
+    }
+    if commands.len() != NUM_PLAYERS { exit_with_usage() }
+
+    let (view, mut state) = match (map_path, seed) {
+        (Some(map_path), None) => match load_map(&map_path) {
+            Ok(map) => map,
+            Err(error) => {
+                eprintln!("Invalid map {}: {}", map_path, error);
+                std::process::exit(1);
+            },
+        },
+        (None, Some(seed)) => {
+            let (layout, state) = mapgen::generate(&mut StdRng::seed_from_u64(seed));
+            (View::new(layout), state)
+        },
+        _ => exit_with_usage(),
+    };
+
+    let mut bots: Vec<Bot> = commands.iter().map(|command| Bot::spawn(command, &view.layout, verbose)).collect();
+    for (player, bot) in bots.iter_mut().enumerate() {
+        bot.send(|input| interface::write_initial(&view.layout, player, input));
+    }
+
+    let outcome = loop {
+        for (player, bot) in bots.iter_mut().enumerate() {
+            bot.send(|input| interface::write_turn(&state, player, input));
+        }
+
+        // Both bots think at the same time, so they share one deadline
+        let deadline = Instant::now() + timeout;
+        let mut forfeits = Vec::new();
+        let mut beacons: AssignmentsPerPlayer = [Box::default(), Box::default()];
+        for (player, bot) in bots.iter().enumerate() {
+            match bot.receive(deadline) {
+                Ok(actions) => {
+                    beacons[player] = movement::actions_to_beacons(player, &actions, &view, &state);
+                },
+                Err(error) => {
+                    println!("Tick {}: player {} forfeits: {}", state.tick, player + 1, error);
+                    forfeits.push(player);
+                },
+            }
+        }
+        match forfeits.len() {
+            0 => (),
+            1 => break Outcome::Win { player: (forfeits[0] + 1) % NUM_PLAYERS, reason: EndReason::Forfeit, by_ants: false },
+            _ => break Outcome::Draw { reason: EndReason::Forfeit },
+        }
+
+        simulation::forward(&beacons, MovementModel::Referee, &view, &mut state);
+        if verbose {
+            println!("Tick {}: crystals=[{} vs {}], ants=[{} vs {}]", state.tick, state.crystals[ME], state.crystals[ENEMY], state.total_ants[ME], state.total_ants[ENEMY]);
+        }
+
+        if let Some(outcome) = view::find_outcome(&view, &state) {
+            break outcome;
+        }
+    };
+
+    println!(
+        "Final: tick={}, crystals=[{} vs {}], ants=[{} vs {}]",
+        state.tick,
+        state.crystals[ME], state.crystals[ENEMY],
+        state.total_ants[ME], state.total_ants[ENEMY],
+    );
+    match outcome {
+        Outcome::Win { player, reason, by_ants } => println!("Winner: player {} ({}) by {}{}", player + 1, commands[player], reason, if by_ants { ", tiebreak on ants" } else { "" }),
+        Outcome::Draw { reason } => println!("Draw by {}", reason),
+    }
+}
+
+/// A map file is an initial input followed by the first turn's input, both from the first player's perspective
+fn load_map(path: &str) -> Result<(View,State), ProtocolError> {
+    let file = File::open(path).unwrap_or_else(|error| {
+        eprintln!("Unable to open {}: {}", path, error);
+        std::process::exit(1);
+    });
+    let mut input = ProtocolReader::new(BufReader::new(file));
+
+    let layout = interface::read_initial(&mut input)?;
+    let TurnInput { crystals_per_player, num_ants_per_cell, resources_per_cell } = interface::read_turn(&mut input, &layout)?;
+    let state = State::new(0, num_ants_per_cell, resources_per_cell, crystals_per_player);
+    Ok((View::new(layout), state))
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
@@ -0,0 +1,110 @@
+use spring_challenge_2023::agent::{Agent,AgentConfig};
+use spring_challenge_2023::interface::{self,ProtocolErrorKind,ProtocolReader,TurnInput};
+use spring_challenge_2023::recording::Replay;
+use spring_challenge_2023::view::*;
+
+const USAGE: &str = "Usage: replay <replay file> [--from <tick>] [--to <tick>] [--iterations <solver steps per tick>]";
+
+/// Used when neither the recording nor the command line gives a number of iterations.
+/// Replays must not search on a time budget or the output would depend on how fast this machine is.
+const DEFAULT_ITERATIONS: u32 = 100;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let path = args.first().filter(|arg| !arg.starts_with("--")).unwrap_or_else(|| exit_with_usage());
+    let from = parse_flag(&args, "--from").unwrap_or(0);
+    let to = parse_flag(&args, "--to").unwrap_or(u32::MAX);
+
+    let replay = match Replay::load(path) {
+        Ok(replay) => replay,
+        Err(error) => {
+            eprintln!("Unable to load {}: {}", path, error);
+            std::process::exit(1);
+        },
+    };
+
+    let mut input = ProtocolReader::new(replay.input.as_bytes());
+    let layout = match interface::read_initial(&mut input) {
+        Ok(layout) => layout,
+        Err(error) => {
+            eprintln!("Invalid replay: {}", error);
+            std::process::exit(1);
+        },
+    };
+    let view = View::new(layout);
+
+    let mut config = AgentConfig::default();
+    if let Some(seed) = replay.config_value("seed") {
+        config.seed = seed.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid replay: seed {}", seed);
+            std::process::exit(1);
+        });
+    }
+    let recorded_iterations = replay.config_value("iterations").and_then(|iterations| iterations.parse().ok());
+    let iterations = parse_flag(&args, "--iterations").or(recorded_iterations).unwrap_or(DEFAULT_ITERATIONS);
+    if recorded_iterations.is_none() {
+        println!("The recording was not made with a fixed number of iterations, so it may not be reproduced exactly. Using {} iterations per tick.", iterations);
+    }
+    config.iterations = Some(iterations);
+    config.explain_desyncs = true;
+
+    // The agent carries its plans from one turn to the next, so every turn before the range is re-run too
+    let mut agent = Agent::with_config(&view, config);
+    let mut num_differences = 0;
+    let mut tick = 0;
+    while tick <= to {
+        let TurnInput { crystals_per_player, num_ants_per_cell, resources_per_cell } = match interface::read_turn(&mut input, &view.layout) {
+            Ok(turn) => turn,
+            Err(error) => {
+                if !matches!(error.kind, ProtocolErrorKind::EndOfInput) {
+                    eprintln!("Invalid replay: {}", error);
+                }
+                break;
+            },
+        };
+        let state = State::new(tick, num_ants_per_cell, resources_per_cell, crystals_per_player);
+
+        let actions = interface::format_actions(&agent.act(&view, &state));
+        if tick >= from {
+            println!("Tick {}: {}", tick, actions);
+
+            let recorded = replay.outputs.get(tick as usize).map(|output| output.as_str()).unwrap_or("");
+            for (sign, command) in diff_commands(recorded, &actions) {
+                println!("  {} {}", sign, command);
+                num_differences += 1;
+            }
+        }
+
+        tick += 1;
+    }
+
+    println!("{} commands differ from the recording", num_differences);
+}
+
+/// Commands only in the recording are marked "-", commands only in the new output are marked "+".
+/// Messages are ignored because they contain the number of iterations, which will always vary.
+fn diff_commands<'a>(recorded: &'a str, actual: &'a str) -> Vec<(char, &'a str)> {
+    let split = |line: &'a str| -> Vec<&'a str> {
+        line.split(';').map(|command| command.trim()).filter(|command| !command.is_empty() && !command.starts_with("MESSAGE")).collect()
+    };
+    let recorded = split(recorded);
+    let actual = split(actual);
+
+    let mut differences = Vec::new();
+    differences.extend(recorded.iter().filter(|command| !actual.contains(command)).map(|command| ('-', *command)));
+    differences.extend(actual.iter().filter(|command| !recorded.contains(command)).map(|command| ('+', *command)));
+    differences
+}
+
+fn parse_flag(args: &[String], flag: &str) -> Option<u32> {
+    let index = args.iter().position(|arg| arg == flag)?;
+    match args.get(index + 1).and_then(|value| value.parse().ok()) {
+        Some(value) => Some(value),
+        None => exit_with_usage(),
+    }
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
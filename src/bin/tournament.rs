@@ -0,0 +1,246 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize,Ordering};
+
+use rand::prelude::*;
+
+use spring_challenge_2023::agent::{Agent,AgentConfig};
+use spring_challenge_2023::inputs::*;
+use spring_challenge_2023::mapgen;
+use spring_challenge_2023::movement::{self,AssignmentsPerPlayer,MovementModel};
+use spring_challenge_2023::recording;
+use spring_challenge_2023::simulation;
+use spring_challenge_2023::view::{self,*};
+
+const USAGE: &str = "Usage: tournament [--games <maps per pairing>] [--seed <first map seed>] [--threads <n>] --agent <config> --agent <config> ...
+  <config> is a comma-separated list of seed=<n>, iterations=<n|time>, search=<ms>, adversary=<ms>, power=<n>, rate=<f>, lines=<true|false>, or \"default\"";
+
+/// Solver steps per tick for each player unless a config asks for time budgets.
+/// Games run on many threads at once, so time budgets would depend on how busy the machine is rather than only on the config.
+const DEFAULT_ITERATIONS: u32 = 100;
+
+const Z_95: f32 = 1.96;
+const RATING_ITERATIONS: usize = 1000;
+
+struct Contestant {
+    label: String,
+    config: AgentConfig,
+}
+
+struct Game {
+    map_seed: u64,
+    players: [usize; NUM_PLAYERS], // contestant ids
+}
+
+#[derive(Clone,Copy,Default)]
+struct Record {
+    wins: i32,
+    draws: i32,
+    losses: i32,
+}
+impl Record {
+    fn num_games(&self) -> i32 { self.wins + self.draws + self.losses }
+    fn points(&self) -> f32 { self.wins as f32 + 0.5 * self.draws as f32 }
+}
+
+fn main() {
+    let mut num_maps = 10;
+    let mut first_seed = 0;
+    let mut num_threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut contestants = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| exit_with_usage());
+        match arg.as_str() {
+            "--games" => num_maps = value.parse().unwrap_or_else(|_| exit_with_usage()),
+            "--seed" => first_seed = value.parse().unwrap_or_else(|_| exit_with_usage()),
+            "--threads" => num_threads = value.parse().unwrap_or_else(|_| exit_with_usage()),
+            "--agent" => contestants.push(Contestant {
+                config: parse_config(&value).unwrap_or_else(|| exit_with_usage()),
+                label: value,
+            }),
+            _ => exit_with_usage(),
+        }
+    }
+    if contestants.len() < 2 { exit_with_usage() }
+
+    // Every pairing plays every map twice, once from each side
+    let mut games = Vec::new();
+    for map in 0..num_maps {
+        for a in 0..contestants.len() {
+            for b in (a + 1)..contestants.len() {
+                games.push(Game { map_seed: first_seed + map, players: [a, b] });
+                games.push(Game { map_seed: first_seed + map, players: [b, a] });
+            }
+        }
+    }
+
+    let records = Mutex::new(vec![vec![Record::default(); contestants.len()]; contestants.len()]);
+    let next_game = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        for _ in 0..num_threads.max(1) {
+            scope.spawn(|| {
+                recording::set_quiet(true);
+                loop {
+                    let index = next_game.fetch_add(1, Ordering::Relaxed);
+                    let game = match games.get(index) {
+                        Some(game) => game,
+                        None => break,
+                    };
+
+                    let configs = game.players.map(|id| contestants[id].config.clone());
+                    let outcome = play(game.map_seed, configs);
+
+                    let [a, b] = game.players;
+                    let mut records = records.lock().expect("poisoned records");
+                    match outcome.winner() {
+                        Some(ME) => {
+                            records[a][b].wins += 1;
+                            records[b][a].losses += 1;
+                        },
+                        Some(_) => {
+                            records[a][b].losses += 1;
+                            records[b][a].wins += 1;
+                        },
+                        None => {
+                            records[a][b].draws += 1;
+                            records[b][a].draws += 1;
+                        },
+                    }
+
+                    let winner_label = outcome.winner().map(|player| contestants[game.players[player]].label.as_str()).unwrap_or("draw");
+                    eprintln!("Game {}/{}: map {}, {} vs {} -> {} by {}", index + 1, games.len(), game.map_seed, contestants[a].label, contestants[b].label, winner_label, outcome.reason());
+                }
+            });
+        }
+    });
+
+    report(&contestants, &records.into_inner().expect("poisoned records"));
+}
+
+fn play(map_seed: u64, configs: [AgentConfig; NUM_PLAYERS]) -> Outcome {
+    let (layout, mut state) = mapgen::generate(&mut StdRng::seed_from_u64(map_seed));
+
+    // Each agent believes it is ME, so the second player sees the map with the bases swapped
+    let mut enemy_layout = layout.clone();
+    enemy_layout.bases.swap(ME, ENEMY);
+    let views = [View::new(layout), View::new(enemy_layout)];
+
+    let [my_config, enemy_config] = configs;
+    let mut agents = [
+        Agent::with_config(&views[ME], my_config),
+        Agent::with_config(&views[ENEMY], enemy_config),
+    ];
+
+    loop {
+        let mut beacons: AssignmentsPerPlayer = [Box::default(), Box::default()];
+        for player in 0..NUM_PLAYERS {
+            let actions = agents[player].act(&views[player], &from_perspective(player, &state));
+            beacons[player] = movement::actions_to_beacons(player, &actions, &views[ME], &state);
+        }
+
+        simulation::forward(&beacons, MovementModel::Referee, &views[ME], &mut state);
+        if let Some(outcome) = view::find_outcome(&views[ME], &state) {
+            return outcome;
+        }
+    }
+}
+
+fn from_perspective(player: usize, state: &State) -> State {
+    let mut state = state.clone();
+    if player != ME {
+        state.num_ants.swap(ME, ENEMY);
+        state.total_ants.swap(ME, ENEMY);
+        state.crystals.swap(ME, ENEMY);
+    }
+    state
+}
+
+fn report(contestants: &[Contestant], records: &[Vec<Record>]) {
+    println!("Pairings:");
+    for a in 0..contestants.len() {
+        for b in (a + 1)..contestants.len() {
+            let record = records[a][b];
+            let n = record.num_games();
+            if n <= 0 { continue }
+
+            let score = record.points() / n as f32;
+            let margin = Z_95 * (score * (1.0 - score) / n as f32).sqrt();
+            println!(
+                "  {} vs {}: {}W {}D {}L, score {:.1}% ± {:.1}%, Elo {:+.0} [{:+.0}, {:+.0}]",
+                contestants[a].label, contestants[b].label,
+                record.wins, record.draws, record.losses,
+                100.0 * score, 100.0 * margin,
+                elo_difference(score, n), elo_difference(score - margin, n), elo_difference(score + margin, n),
+            );
+        }
+    }
+
+    println!("Ratings (relative to {}):", contestants[0].label);
+    let ratings = fit_ratings(records);
+    let mut order: Vec<usize> = (0..contestants.len()).collect();
+    order.sort_by(|&a, &b| ratings[b].partial_cmp(&ratings[a]).expect("invalid rating"));
+    for id in order {
+        let record = records[id].iter().fold(Record::default(), |total, r| Record {
+            wins: total.wins + r.wins,
+            draws: total.draws + r.draws,
+            losses: total.losses + r.losses,
+        });
+        println!("  {:+6.0} {} ({}W {}D {}L)", ratings[id] - ratings[0], contestants[id].label, record.wins, record.draws, record.losses);
+    }
+}
+
+/// The Elo difference implied by an expected score, clamped so that a perfect score does not become infinite
+fn elo_difference(score: f32, num_games: i32) -> f32 {
+    let limit = 0.5 / num_games.max(1) as f32;
+    let score = score.clamp(limit, 1.0 - limit);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// Maximum likelihood Bradley-Terry ratings on the Elo scale, counting draws as half a win.
+/// Every pairing gets one extra virtual draw so that unbeaten or winless contestants still have finite ratings.
+fn fit_ratings(records: &[Vec<Record>]) -> Vec<f32> {
+    let num_contestants = records.len();
+    let mut strengths = vec![1.0f32; num_contestants];
+    for _ in 0..RATING_ITERATIONS {
+        for i in 0..num_contestants {
+            let mut points = 0.0;
+            let mut denominator = 0.0;
+            for j in 0..num_contestants {
+                if i == j { continue }
+                let record = records[i][j];
+                points += record.points() + 0.5;
+                denominator += (record.num_games() + 1) as f32 / (strengths[i] + strengths[j]);
+            }
+            if denominator > 0.0 {
+                strengths[i] = points / denominator;
+            }
+        }
+    }
+    strengths.iter().map(|strength| 400.0 * strength.log10()).collect()
+}
+
+fn parse_config(spec: &str) -> Option<AgentConfig> {
+    let mut config = AgentConfig { iterations: Some(DEFAULT_ITERATIONS), ..AgentConfig::default() };
+    for setting in spec.split(',') {
+        if setting == "default" { continue }
+
+        let (key, value) = setting.split_once('=')?;
+        match key {
+            "seed" => config.seed = value.parse().ok()?,
+            "iterations" => config.iterations = if value == "time" { None } else { Some(value.parse().ok()?) },
+            "search" => config.search_ms = value.parse().ok()?,
+            "adversary" => config.adversary_ms = value.parse().ok()?,
+            "power" => config.solver.selection_power = value.parse().ok()?,
+            "rate" => config.solver.learning_rate = value.parse().ok()?,
+            "lines" => config.use_lines = value.parse().ok()?,
+            _ => return None,
+        }
+    }
+    Some(config)
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
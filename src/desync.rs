@@ -0,0 +1,97 @@
+use std::fmt::Display;
+
+use super::inputs::*;
+use super::movement::{AssignmentsPerPlayer,MovementModel};
+use super::simulation::{self,Event};
+use super::view::*;
+
+/// Where the simulated next state differs from what was actually observed
+pub struct Desync {
+    pub tick: u32,
+    pub expected_crystals: CrystalsPerPlayer,
+    pub actual_crystals: CrystalsPerPlayer,
+    pub cells: Vec<CellDesync>,
+
+    /// What happened in the simulated tick, to explain the expected state. Only displayed with the alternate format {:#}.
+    pub events: Vec<Event>,
+}
+impl Desync {
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty() && self.expected_crystals == self.actual_crystals
+    }
+}
+impl Display for Desync {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f, "Desync at tick {}: crystals expected [{} vs {}], actual [{} vs {}]",
+            self.tick,
+            self.expected_crystals[ME], self.expected_crystals[ENEMY],
+            self.actual_crystals[ME], self.actual_crystals[ENEMY])?;
+        for cell in self.cells.iter() {
+            write!(f, "\n  {}", cell)?;
+        }
+        if f.alternate() {
+            for event in self.events.iter() {
+                write!(f, "\n  simulated: {}", event)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct CellDesync {
+    pub cell: usize,
+    pub expected_ants: [i32; NUM_PLAYERS],
+    pub actual_ants: [i32; NUM_PLAYERS],
+    pub expected_resources: i32,
+    pub actual_resources: i32,
+}
+impl Display for CellDesync {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cell {}:", self.cell)?;
+        if self.expected_ants != self.actual_ants {
+            write!(
+                f, " ants expected [{} vs {}], actual [{} vs {}]",
+                self.expected_ants[ME], self.expected_ants[ENEMY],
+                self.actual_ants[ME], self.actual_ants[ENEMY])?;
+        }
+        if self.expected_resources != self.actual_resources {
+            write!(f, " resources expected {}, actual {}", self.expected_resources, self.actual_resources)?;
+        }
+        Ok(())
+    }
+}
+
+/// Simulate one tick forward from the previous state and compare it against the state that was actually observed.
+/// This uses the same movement model as the rollouts, so shows where the rollouts diverge from the real game.
+pub fn detect(previous: &State, assignments: &AssignmentsPerPlayer, view: &View, observed: &State) -> Desync {
+    let mut expected = previous.clone();
+    let mut events = Vec::new();
+    simulation::forward_with_log(assignments, MovementModel::MinCost, view, &mut expected, &mut events);
+
+    let mut cells = Vec::new();
+    for cell in 0..view.layout.cells.len() {
+        let expected_ants = [expected.num_ants[ME][cell], expected.num_ants[ENEMY][cell]];
+        let actual_ants = [observed.num_ants[ME][cell], observed.num_ants[ENEMY][cell]];
+        let expected_resources = expected.resources[cell];
+        let actual_resources = observed.resources[cell];
+
+        if expected_ants != actual_ants || expected_resources != actual_resources {
+            cells.push(CellDesync {
+                cell,
+                expected_ants,
+                actual_ants,
+                expected_resources,
+                actual_resources,
+            });
+        }
+    }
+
+    Desync {
+        tick: observed.tick,
+        expected_crystals: expected.crystals,
+        actual_crystals: observed.crystals,
+        cells,
+        events,
+    }
+}
@@ -1,3 +1,4 @@
+use super::movement::MovementModel;
 use super::planning::{self,*};
 use super::inputs::*;
 use super::simulation;
@@ -6,20 +7,21 @@ use super::view::{self,*};
 const NUM_TICKS: u32 = 100;
 const DECAY_RATE: f32 = 0.98;
 const WIN_PAYOFF: f32 = 1.0;
+const DRAW_PAYOFF: f32 = 0.0;
 
 #[derive(Clone,Debug)]
 pub struct Endgame {
     pub tick: u32,
     pub crystals: CrystalsPerPlayer,
     pub total_ants: [i32; NUM_PLAYERS],
-    pub winner: Option<usize>,
+    pub outcome: Option<Outcome>,
 }
 
 pub fn rollout(plans: [&Vec<Milestone>; NUM_PLAYERS], view: &View, state: &State) -> (f32,Endgame) {
     let mut payoff = 0.0;
 
     let mut state = state.clone();
-    let mut winner = None;
+    let mut outcome = None;
     for age in 0..NUM_TICKS {
         let Commands { assignments: my_assignments, .. } = planning::enact_plan(ME, &plans[ME], view, &state);
         let Commands { assignments: enemy_assignments, .. } = planning::enact_plan(ENEMY, &plans[ENEMY], view, &state);
@@ -30,15 +32,15 @@ pub fn rollout(plans: [&Vec<Milestone>; NUM_PLAYERS], view: &View, state: &State
         ];
 
         let initial_crystals = state.crystals.clone();
-        simulation::forward(&assignments, view, &mut state);
+        simulation::forward(&assignments, MovementModel::MinCost, view, &mut state);
 
         for player in 0..NUM_PLAYERS {
             payoff += evaluate_harvesting(player, state.crystals[player], initial_crystals[player], age);
         }
 
-        if let Some(w) = view::find_winner(view, &state) {
-            payoff += evaluate_win(w, age);
-            winner = Some(w);
+        if let Some(o) = view::find_outcome(view, &state) {
+            payoff += evaluate_outcome(o, age);
+            outcome = Some(o);
             break;
         }
 
@@ -49,7 +51,7 @@ pub fn rollout(plans: [&Vec<Milestone>; NUM_PLAYERS], view: &View, state: &State
         tick: state.tick,
         crystals: state.crystals,
         total_ants: state.total_ants,
-        winner,
+        outcome,
     };
     (payoff, endgame)
 }
@@ -63,8 +65,12 @@ fn evaluate_harvesting(player: usize, num_crystals: i32, previous_crystals: i32,
     mined as f32 * evaluate_player(player) * discount(age)
 }
 
-fn evaluate_win(player: usize, age: u32) -> f32 {
-    WIN_PAYOFF * evaluate_player(player) * discount(age)
+fn evaluate_outcome(outcome: Outcome, age: u32) -> f32 {
+    let payoff = match outcome {
+        Outcome::Win { player, .. } => WIN_PAYOFF * evaluate_player(player),
+        Outcome::Draw { .. } => DRAW_PAYOFF,
+    };
+    payoff * discount(age)
 }
 
 fn evaluate_player(player: usize) -> f32 {
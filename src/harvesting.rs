@@ -1,18 +1,27 @@
-use std::collections::VecDeque;
+use std::collections::BinaryHeap;
 
 use super::inputs::*;
+use super::simulation::{Event,EventLog};
 use super::view::*;
 
 pub struct HarvestMap {
     max_flow_per_player: [Box<[i32]>; NUM_PLAYERS],
 }
 impl HarvestMap {
-    pub fn generate(view: &View, num_ants: &AntsPerCellPerPlayer) -> Self {
+    /// Resolves chains the same way as the referee: attack chains are calculated once from every ant on the board,
+    /// then harvest chains can only pass through cells where the opponent's attack chain is not stronger.
+    pub fn generate(view: &View, num_ants: &AntsPerCellPerPlayer, log: &mut impl EventLog) -> Self {
         let max_flow_per_player = [
             calculate_max_flow_for_player(ME, view, num_ants),
             calculate_max_flow_for_player(ENEMY, view, num_ants),
         ];
 
+        for cell in 0..view.layout.cells.len() {
+            if num_ants[ME][cell] > 0 && num_ants[ENEMY][cell] > 0 {
+                log.log(Event::Contested { cell, attack_chains: [max_flow_per_player[ME][cell], max_flow_per_player[ENEMY][cell]] });
+            }
+        }
+
         let max_flow_per_player = match calculate_unhindered_ants(&max_flow_per_player, num_ants) {
             Some(num_unhindered_ants) => [
                 calculate_max_flow_for_player(ME, view, &num_unhindered_ants),
@@ -37,29 +46,28 @@ pub fn calculate_max_flow_for_player(player: usize, view: &View, num_ants: &Ants
     calculate_flows_to_base(view.layout.bases[player].iter().cloned(), &view.layout, &num_ants[player])
 }
 
+/// The strength of the strongest chain of ants from any base to each cell, where a chain is as strong as its weakest cell.
+/// This is a widest path search: cells are finalized strongest first, so each is visited once with its exact value.
 fn calculate_flows_to_base(bases: impl Iterator<Item=usize>, layout: &Layout, num_ants: &AntsPerCell) -> Box<[i32]> {
-    let mut flows = Vec::new();
-    flows.resize(layout.cells.len(), 0);
+    let mut flows = vec![0; layout.cells.len()];
 
-    let mut queue = VecDeque::new();
+    let mut queue = BinaryHeap::new();
     for base in bases {
-        flows[base] = num_ants[base];
-        queue.push_back(base);
+        let base_flow = num_ants[base];
+        if base_flow > flows[base] {
+            flows[base] = base_flow;
+            queue.push((base_flow, base));
+        }
     }
 
-    while let Some(source) = queue.pop_front() {
-        let source_flow = flows[source];
-        if source_flow < 0 { continue }
+    while let Some((source_flow, source)) = queue.pop() {
+        if source_flow < flows[source] { continue } // Already finalized with a stronger chain
 
         for &neighbor in layout.cells[source].neighbors.iter() {
-            let neighbor_ants = num_ants[neighbor];
-            if neighbor_ants <= 0 { continue }
-
-            let neighbor_flow = neighbor_ants.min(source_flow);
-
+            let neighbor_flow = num_ants[neighbor].min(source_flow);
             if flows[neighbor] < neighbor_flow {
                 flows[neighbor] = neighbor_flow;
-                queue.push_back(neighbor);
+                queue.push((neighbor_flow, neighbor));
             }
         }
     }
@@ -67,6 +75,7 @@ fn calculate_flows_to_base(bases: impl Iterator<Item=usize>, layout: &Layout, nu
     flows.into_boxed_slice()
 }
 
+/// Removes the ants on each cell where the opponent's attack chain is stronger, or returns None if neither chain is stronger anywhere
 fn calculate_unhindered_ants(attack_chains_per_player: &[Box<[i32]>; NUM_PLAYERS], num_ants: &AntsPerCellPerPlayer) -> Option<AntsPerCellPerPlayer> {
     let mut unhindered = num_ants.clone();
     let num_cells = attack_chains_per_player[ME].len();
@@ -90,4 +99,68 @@ fn calculate_unhindered_ants(attack_chains_per_player: &[Box<[i32]>; NUM_PLAYERS
     } else {
         None
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use super::*;
+    use crate::mapgen;
+
+    /// A slow but obviously correct reference for calculate_max_flow_for_player.
+    /// A chain of strength at least k reaches a cell exactly when the cell is connected to a base through cells with at least k ants,
+    /// so try every possible strength and keep the strongest that reaches each cell.
+    fn calculate_max_flow_for_player_brute_force(player: usize, view: &View, num_ants: &AntsPerCellPerPlayer) -> Box<[i32]> {
+        let layout = &view.layout;
+        let num_ants = &num_ants[player];
+        let mut flows = vec![0; layout.cells.len()];
+
+        let mut strengths: Vec<i32> = num_ants.iter().cloned().filter(|&ants| ants > 0).collect();
+        strengths.sort();
+        strengths.dedup();
+
+        for strength in strengths {
+            let mut reached = vec![false; layout.cells.len()];
+            let mut stack: Vec<usize> = layout.bases[player].iter().cloned().filter(|&base| num_ants[base] >= strength).collect();
+            for &base in stack.iter() {
+                reached[base] = true;
+            }
+
+            while let Some(cell) = stack.pop() {
+                flows[cell] = strength;
+                for &neighbor in layout.cells[cell].neighbors.iter() {
+                    if !reached[neighbor] && num_ants[neighbor] >= strength {
+                        reached[neighbor] = true;
+                        stack.push(neighbor);
+                    }
+                }
+            }
+        }
+
+        flows.into_boxed_slice()
+    }
+
+    #[test]
+    fn max_flow_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for map_seed in 0..60 {
+            let (layout, _) = mapgen::generate(&mut StdRng::seed_from_u64(map_seed));
+            let view = View::new(layout);
+            let num_cells = view.layout.cells.len();
+
+            for _ in 0..20 {
+                let num_ants: AntsPerCellPerPlayer = [
+                    (0..num_cells).map(|_| if rng.gen_bool(0.5) { rng.gen_range(1..10) } else { 0 }).collect(),
+                    (0..num_cells).map(|_| if rng.gen_bool(0.5) { rng.gen_range(1..10) } else { 0 }).collect(),
+                ];
+                for player in 0..NUM_PLAYERS {
+                    assert_eq!(
+                        calculate_max_flow_for_player(player, &view, &num_ants),
+                        calculate_max_flow_for_player_brute_force(player, &view, &num_ants),
+                        "map {} player {}", map_seed, player);
+                }
+            }
+        }
+    }
+}
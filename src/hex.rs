@@ -0,0 +1,360 @@
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::ops::{Add,Sub};
+use super::fnv::FnvHashMap;
+
+use super::inputs::*;
+
+/// Axial offsets for each direction, in the same order as CellLayout::directions
+pub const DIRECTIONS: [Hex; NUM_DIRECTIONS] = [
+    Hex::new(1, 0), // right
+    Hex::new(1, -1), // top-right
+    Hex::new(0, -1), // top-left
+    Hex::new(-1, 0), // left
+    Hex::new(-1, 1), // bottom-left
+    Hex::new(0, 1), // bottom-right
+];
+
+/// Axial coordinates of a pointy-top hex. The third cube coordinate is implied by q + r + s = 0.
+#[derive(Copy,Clone,Debug,Default,PartialEq,Eq,Hash)]
+pub struct Hex {
+    pub q: i32,
+    pub r: i32,
+}
+impl Hex {
+    pub const fn new(q: i32, r: i32) -> Self {
+        Self { q, r }
+    }
+
+    pub fn s(&self) -> i32 { -self.q - self.r }
+
+    pub fn neighbor(&self, direction: usize) -> Self {
+        *self + DIRECTIONS[direction]
+    }
+
+    pub fn scale(&self, factor: i32) -> Self {
+        Self::new(self.q * factor, self.r * factor)
+    }
+
+    /// Point reflection through the origin
+    pub fn mirror(&self) -> Self {
+        Self::new(-self.q, -self.r)
+    }
+
+    pub fn length(&self) -> i32 {
+        (self.q.abs() + self.r.abs() + self.s().abs()) / 2
+    }
+
+    pub fn distance(&self, other: Hex) -> i32 {
+        (*self - other).length()
+    }
+
+    /// All hexes exactly radius steps away, starting from the bottom-left corner and walking counter-clockwise
+    pub fn ring(&self, radius: i32) -> Vec<Hex> {
+        if radius <= 0 { return vec![*self] }
+
+        let mut ring = Vec::with_capacity(NUM_DIRECTIONS * radius as usize);
+        let mut current = *self + DIRECTIONS[4].scale(radius);
+        for direction in 0..NUM_DIRECTIONS {
+            for _ in 0..radius {
+                ring.push(current);
+                current = current.neighbor(direction);
+            }
+        }
+        ring
+    }
+
+    /// All hexes on the straight line between the two hexes, inclusive of both ends
+    pub fn line(&self, other: Hex) -> Vec<Hex> {
+        let num_steps = self.distance(other);
+        if num_steps == 0 { return vec![*self] }
+
+        // Nudge the endpoints so points exactly on an edge between two hexes consistently round the same way
+        const EPSILON: f32 = 1e-6;
+        let (q0, r0) = (self.q as f32 + EPSILON, self.r as f32 + EPSILON);
+        let (q1, r1) = (other.q as f32 + EPSILON, other.r as f32 + EPSILON);
+
+        (0..=num_steps).map(|step| {
+            let t = step as f32 / num_steps as f32;
+            Self::round(q0 + (q1 - q0) * t, r0 + (r1 - r0) * t)
+        }).collect()
+    }
+
+    fn round(q: f32, r: f32) -> Hex {
+        let s = -q - r;
+
+        let mut rounded_q = q.round();
+        let mut rounded_r = r.round();
+        let rounded_s = s.round();
+
+        // Fix whichever coordinate was rounded the most so that q + r + s = 0 still holds
+        let dq = (rounded_q - q).abs();
+        let dr = (rounded_r - r).abs();
+        let ds = (rounded_s - s).abs();
+        if dq > dr && dq > ds {
+            rounded_q = -rounded_r - rounded_s;
+        } else if dr > ds {
+            rounded_r = -rounded_q - rounded_s;
+        }
+
+        Hex::new(rounded_q as i32, rounded_r as i32)
+    }
+}
+impl Add for Hex {
+    type Output = Hex;
+    fn add(self, other: Hex) -> Hex { Hex::new(self.q + other.q, self.r + other.r) }
+}
+impl Sub for Hex {
+    type Output = Hex;
+    fn sub(self, other: Hex) -> Hex { Hex::new(self.q - other.q, self.r - other.r) }
+}
+impl Display for Hex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({},{})", self.q, self.r)
+    }
+}
+
+/// Why a Layout's neighbor directions cannot be drawn on a hex grid
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum HexConflict {
+    /// The neighbor directions place a second cell on a hex that already has one
+    SharedHex { hex: Hex, cell: usize, other: usize },
+
+    /// The neighbor was already placed on a hex that is not in this direction from the cell
+    MisplacedNeighbor { cell: usize, direction: usize, neighbor: usize },
+}
+impl Display for HexConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SharedHex { hex, cell, other } => write!(f, "cells {} and {} both placed at {}", cell, other, hex),
+            Self::MisplacedNeighbor { cell, direction, neighbor } => write!(f, "cell {} direction {}: neighbor {} already placed elsewhere", cell, direction, neighbor),
+        }
+    }
+}
+
+/// Hex coordinates for every cell of a Layout, found by walking the neighbor directions outwards from cell 0.
+/// Cell 0 is the centre of the contest maps, so it is placed at the origin.
+pub struct HexMap {
+    /// cell -> coordinate, None if the cell is unreachable from cell 0
+    coordinates: Box<[Option<Hex>]>,
+
+    /// coordinate -> cell
+    lookup: FnvHashMap<Hex, usize>,
+}
+impl HexMap {
+    /// Fails with every conflict found if the neighbor directions are not consistent with a hex grid
+    pub fn generate(layout: &Layout) -> Result<Self,Vec<HexConflict>> {
+        let num_cells = layout.cells.len();
+        let mut coordinates = vec![None; num_cells];
+        let mut lookup = FnvHashMap::default();
+        let mut conflicts = Vec::new();
+
+        let mut queue = VecDeque::new();
+        if num_cells > 0 {
+            coordinates[0] = Some(Hex::default());
+            lookup.insert(Hex::default(), 0);
+            queue.push_back(0);
+        }
+
+        while let Some(cell) = queue.pop_front() {
+            let hex = coordinates[cell].expect("queued cell missing coordinate");
+            for (direction, neighbor) in layout.cells[cell].directions.iter().enumerate() {
+                let neighbor = match neighbor {
+                    Some(neighbor) => *neighbor,
+                    None => continue,
+                };
+
+                let neighbor_hex = hex.neighbor(direction);
+                if let Some(placed) = coordinates[neighbor] {
+                    if placed != neighbor_hex {
+                        conflicts.push(HexConflict::MisplacedNeighbor { cell, direction, neighbor });
+                    }
+                    continue;
+                }
+
+                coordinates[neighbor] = Some(neighbor_hex);
+                match lookup.get(&neighbor_hex) {
+                    Some(&other) => conflicts.push(HexConflict::SharedHex { hex: neighbor_hex, cell: neighbor, other }),
+                    None => { lookup.insert(neighbor_hex, neighbor); },
+                }
+                queue.push_back(neighbor);
+            }
+        }
+        if !conflicts.is_empty() { return Err(conflicts) }
+
+        Ok(Self {
+            coordinates: coordinates.into_boxed_slice(),
+            lookup,
+        })
+    }
+
+    pub fn coordinate(&self, cell: usize) -> Option<Hex> {
+        self.coordinates[cell]
+    }
+
+    pub fn cell_at(&self, hex: Hex) -> Option<usize> {
+        self.lookup.get(&hex).cloned()
+    }
+
+    /// Straight-line hex distance ignoring holes in the map, so always a lower bound on the path distance
+    pub fn distance_between(&self, a: usize, b: usize) -> Option<i32> {
+        Some(self.coordinates[a]?.distance(self.coordinates[b]?))
+    }
+
+    /// The cell on the opposite side of the map, assuming the map is point-symmetric around cell 0
+    pub fn mirror(&self, cell: usize) -> Option<usize> {
+        self.cell_at(self.coordinates[cell]?.mirror())
+    }
+
+    /// Cells exactly radius steps away from the center cell, skipping holes in the map
+    pub fn ring(&self, center: usize, radius: i32) -> Vec<usize> {
+        match self.coordinates[center] {
+            Some(hex) => hex.ring(radius).into_iter().filter_map(|h| self.cell_at(h)).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Cells along the straight line between a and b, with None wherever the line crosses a hole in the map
+    pub fn line(&self, a: usize, b: usize) -> Vec<Option<usize>> {
+        match (self.coordinates[a], self.coordinates[b]) {
+            (Some(from), Some(to)) => from.line(to).into_iter().map(|h| self.cell_at(h)).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use super::*;
+    use crate::mapgen;
+    use crate::pathing::PathMap;
+
+    #[test]
+    fn generated_maps_have_consistent_coordinates() {
+        for seed in 0..20 {
+            let (layout, _) = mapgen::generate(&mut StdRng::seed_from_u64(seed));
+            let hexes = HexMap::generate(&layout).expect("generated map should fit a hex grid");
+            for (cell, cell_layout) in layout.cells.iter().enumerate() {
+                let hex = hexes.coordinate(cell).expect("cell missing coordinate");
+                assert_eq!(hexes.cell_at(hex), Some(cell));
+                for (direction, &neighbor) in cell_layout.directions.iter().enumerate() {
+                    if let Some(neighbor) = neighbor {
+                        assert_eq!(hexes.coordinate(neighbor), Some(hex.neighbor(direction)), "map {}: cell {} direction {}", seed, cell, direction);
+                    }
+                }
+
+                // The centre mirrors itself, and every other cell 2k+1 is mirrored by cell 2k+2
+                let expected_mirror = if cell == 0 { 0 } else if cell % 2 == 1 { cell + 1 } else { cell - 1 };
+                assert_eq!(hexes.mirror(cell), Some(expected_mirror), "map {}: cell {}", seed, cell);
+            }
+        }
+    }
+
+    #[test]
+    fn conflicting_directions_are_reported() {
+        let cell = |directions: &[(usize, usize)]| {
+            let mut neighbors = [None; NUM_DIRECTIONS];
+            for &(direction, neighbor) in directions {
+                neighbors[direction] = Some(neighbor);
+            }
+            CellLayout::new(None, 0, neighbors)
+        };
+
+        // Cell 2 is top-right of cell 0, but also right of cell 1 which is right of cell 0, so both ends of that link are wrong
+        let layout = Layout {
+            cells: vec![cell(&[(0, 1), (1, 2)]), cell(&[(3, 0), (0, 2)]), cell(&[(4, 0), (3, 1)])].into_boxed_slice(),
+            bases: [Box::new([0]), Box::new([2])],
+        };
+        assert_eq!(HexMap::generate(&layout).err(), Some(vec![
+            HexConflict::MisplacedNeighbor { cell: 1, direction: 0, neighbor: 2 },
+            HexConflict::MisplacedNeighbor { cell: 2, direction: 3, neighbor: 1 },
+        ]));
+
+        // Cell 3 is top-left of cell 1, which is where cell 2 already is
+        let layout = Layout {
+            cells: vec![cell(&[(0, 1), (1, 2)]), cell(&[(3, 0), (2, 3)]), cell(&[(4, 0)]), cell(&[(5, 1)])].into_boxed_slice(),
+            bases: [Box::new([0]), Box::new([3])],
+        };
+        assert_eq!(HexMap::generate(&layout).err(), Some(vec![HexConflict::SharedHex { hex: Hex::new(1, -1), cell: 3, other: 2 }]));
+    }
+
+    /// A full hexagon with no holes, centred on cell 0
+    fn open_layout(radius: i32) -> Layout {
+        let hexes: Vec<Hex> = (0..=radius).flat_map(|r| Hex::default().ring(r)).collect();
+        let cells: Vec<CellLayout> = hexes.iter().map(|hex| {
+            let mut directions = [None; NUM_DIRECTIONS];
+            for (direction, neighbor) in directions.iter_mut().enumerate() {
+                *neighbor = hexes.iter().position(|&other| other == hex.neighbor(direction));
+            }
+            CellLayout::new(None, 0, directions)
+        }).collect();
+        Layout {
+            cells: cells.into_boxed_slice(),
+            bases: [Box::new([1]), Box::new([4])],
+        }
+    }
+
+    #[test]
+    fn rings_have_six_cells_per_step_of_radius() {
+        let center = Hex::new(2, -1);
+        assert_eq!(center.ring(0), vec![center]);
+        for radius in 1..6 {
+            let ring = center.ring(radius);
+            assert_eq!(ring.len(), NUM_DIRECTIONS * radius as usize);
+            assert!(ring.iter().all(|hex| hex.distance(center) == radius));
+            assert!(ring.iter().enumerate().all(|(i, hex)| !ring[..i].contains(hex)), "ring {} repeats a hex", radius);
+        }
+    }
+
+    #[test]
+    fn lines_join_their_endpoints_through_adjacent_hexes() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..200 {
+            let a = Hex::new(rng.gen_range(-8..=8), rng.gen_range(-8..=8));
+            let b = Hex::new(rng.gen_range(-8..=8), rng.gen_range(-8..=8));
+            let line = a.line(b);
+            assert_eq!(line.len() as i32, a.distance(b) + 1);
+            assert_eq!((line[0], line[line.len() - 1]), (a, b));
+            assert!(line.windows(2).all(|pair| pair[0].distance(pair[1]) == 1), "{} to {}: {:?}", a, b, line);
+        }
+    }
+
+    #[test]
+    fn hex_map_ring_and_line_skip_holes() {
+        let layout = open_layout(3);
+        let hexes = HexMap::generate(&layout).expect("open map should fit a hex grid");
+        assert_eq!(hexes.ring(0, 2).len(), 12);
+        assert_eq!(hexes.line(0, layout.cells.len() - 1).len(), 4);
+        assert!(hexes.line(0, layout.cells.len() - 1).iter().all(|cell| cell.is_some()));
+
+        // Cells beyond the edge of the map are holes
+        let edge = hexes.cell_at(Hex::new(3, 0)).expect("missing edge cell");
+        assert_eq!(hexes.ring(edge, 1).len(), 3);
+    }
+
+    #[test]
+    fn hex_distance_bounds_path_distance() {
+        for seed in 0..20 {
+            let (layout, _) = mapgen::generate(&mut StdRng::seed_from_u64(seed));
+            let hexes = HexMap::generate(&layout).expect("generated map should fit a hex grid");
+            let paths = PathMap::generate(&layout);
+            for a in 0..layout.cells.len() {
+                for b in 0..layout.cells.len() {
+                    assert!(hexes.distance_between(a, b).expect("missing coordinate") <= paths.distance_between(a, b), "map {}: {} to {}", seed, a, b);
+                }
+            }
+        }
+
+        // With no holes the shortest path is a straight line
+        let layout = open_layout(4);
+        let hexes = HexMap::generate(&layout).expect("open map should fit a hex grid");
+        let paths = PathMap::generate(&layout);
+        for a in 0..layout.cells.len() {
+            for b in 0..layout.cells.len() {
+                assert_eq!(hexes.distance_between(a, b), Some(paths.distance_between(a, b)));
+            }
+        }
+    }
+}
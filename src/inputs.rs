@@ -2,6 +2,8 @@ use std::fmt::Display;
 
 pub const MAX_TICKS: u32 = 100;
 
+pub const NUM_DIRECTIONS: usize = 6;
+
 pub const NUM_PLAYERS: usize = 2;
 pub const ME: usize = 0;
 #[allow(dead_code)]
@@ -9,18 +11,37 @@ pub const ENEMY: usize = 1;
 
 pub type CrystalsPerPlayer = [i32; NUM_PLAYERS];
 
+#[derive(Clone,Debug,PartialEq)]
 pub struct Layout {
     pub cells: Box<[CellLayout]>,
     pub bases: [Box<[usize]>; NUM_PLAYERS],
 }
 
+#[derive(Clone,Debug,PartialEq)]
 pub struct CellLayout {
     pub content: Option<Content>,
+
+    /// Neighbors that exist, in direction order
     pub neighbors: Box<[usize]>,
+
+    /// direction -> neighbor, in protocol order: right, then counter-clockwise (top-right, top-left, left, bottom-left, bottom-right)
+    pub directions: [Option<usize>; NUM_DIRECTIONS],
+
     pub initial_resources: i32,
 }
+impl CellLayout {
+    pub fn new(content: Option<Content>, initial_resources: i32, directions: [Option<usize>; NUM_DIRECTIONS]) -> Self {
+        let neighbors: Vec<usize> = directions.iter().flatten().cloned().collect();
+        Self {
+            content,
+            neighbors: neighbors.into_boxed_slice(),
+            directions,
+            initial_resources,
+        }
+    }
+}
 
-#[derive(Copy,Clone,PartialEq,Eq,Hash)]
+#[derive(Copy,Clone,Debug,PartialEq,Eq,Hash)]
 pub enum Content {
     Eggs,
     Crystals,
@@ -34,7 +55,7 @@ impl Display for Content {
     }
 }
 
-#[allow(dead_code)]
+#[derive(Clone,Debug,PartialEq)]
 pub enum Action {
     Beacon { index: usize, strength: i32 },
     Line { source: usize, target: usize, strength: i32 },
@@ -1,5 +1,8 @@
-use std::io;
+use std::fmt::Display;
+use std::io::{self,BufRead,Write};
+use std::str::FromStr;
 use super::inputs::*;
+use super::view::State;
 
 pub struct TurnInput {
     pub crystals_per_player: CrystalsPerPlayer,
@@ -7,105 +10,247 @@ pub struct TurnInput {
     pub resources_per_cell: Box<[i32]>,
 }
 
-macro_rules! parse_input {
-    ($x:expr, $t:ident) => ($x.trim().parse::<$t>().unwrap())
+#[derive(Debug)]
+pub struct ProtocolError {
+    /// 1-based line number within the input stream
+    pub line: usize,
+    pub field: &'static str,
+    pub text: String,
+    pub kind: ProtocolErrorKind,
 }
 
-pub fn read_initial() -> Layout {
+#[derive(Debug)]
+pub enum ProtocolErrorKind {
+    EndOfInput,
+    MissingField,
+    InvalidValue,
+    Io(io::ErrorKind),
+}
+
+impl Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            ProtocolErrorKind::EndOfInput => write!(f, "line {}: unexpected end of input while reading {}", self.line, self.field),
+            ProtocolErrorKind::MissingField => write!(f, "line {}: missing {} in \"{}\"", self.line, self.field, self.text),
+            ProtocolErrorKind::InvalidValue => write!(f, "line {}: invalid {} \"{}\"", self.line, self.field, self.text),
+            ProtocolErrorKind::Io(kind) => write!(f, "line {}: failed to read {}: {}", self.line, self.field, kind),
+        }
+    }
+}
+impl std::error::Error for ProtocolError {}
+
+/// Reads the protocol line by line from any source (stdin, files, in-memory strings),
+/// keeping track of the line number so errors can point at the offending input
+pub struct ProtocolReader<R> {
+    reader: R,
+    line_number: usize,
+}
+#[allow(dead_code)]
+impl ProtocolReader<io::StdinLock<'static>> {
+    pub fn stdin() -> Self {
+        Self::new(io::stdin().lock())
+    }
+}
+impl<R: BufRead> ProtocolReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, line_number: 0 }
+    }
+
+    #[allow(dead_code)]
+    pub fn line_number(&self) -> usize { self.line_number }
+
+    #[allow(dead_code)]
+    pub fn into_inner(self) -> R { self.reader }
+
+    fn next_line(&mut self, field: &'static str) -> Result<Line, ProtocolError> {
+        self.line_number += 1;
+
+        let mut text = String::new();
+        match self.reader.read_line(&mut text) {
+            Ok(0) => Err(ProtocolError { line: self.line_number, field, text, kind: ProtocolErrorKind::EndOfInput }),
+            Ok(_) => Ok(Line::new(self.line_number, text)),
+            Err(error) => Err(ProtocolError { line: self.line_number, field, text, kind: ProtocolErrorKind::Io(error.kind()) }),
+        }
+    }
+}
+
+struct Line {
+    number: usize,
+    text: String,
+    next_token: usize,
+}
+impl Line {
+    fn new(number: usize, text: String) -> Self {
+        Self { number, text, next_token: 0 }
+    }
+
+    fn next<T: FromStr>(&mut self, field: &'static str) -> Result<T, ProtocolError> {
+        let token = match self.text.split_whitespace().nth(self.next_token) {
+            Some(token) => token,
+            None => return Err(self.error(field, self.text.trim().to_string(), ProtocolErrorKind::MissingField)),
+        };
+        self.next_token += 1;
+
+        token.parse::<T>().map_err(|_| self.error(field, token.to_string(), ProtocolErrorKind::InvalidValue))
+    }
+
+    #[allow(dead_code)]
+    fn next_cell(&mut self, field: &'static str, layout: &Layout) -> Result<usize, ProtocolError> {
+        let cell = self.next::<usize>(field)?;
+        if cell >= layout.cells.len() {
+            return Err(self.error(field, cell.to_string(), ProtocolErrorKind::InvalidValue));
+        }
+        Ok(cell)
+    }
+
+    #[allow(dead_code)]
+    fn next_strength(&mut self) -> Result<i32, ProtocolError> {
+        let strength = self.next::<i32>("strength")?;
+        if strength <= 0 {
+            return Err(self.error("strength", strength.to_string(), ProtocolErrorKind::InvalidValue));
+        }
+        Ok(strength)
+    }
+
+    fn expect_end(&self) -> Result<(), ProtocolError> {
+        match self.text.split_whitespace().nth(self.next_token) {
+            Some(token) => Err(self.error("trailing argument", token.to_string(), ProtocolErrorKind::InvalidValue)),
+            None => Ok(()),
+        }
+    }
+
+    fn error(&self, field: &'static str, text: String, kind: ProtocolErrorKind) -> ProtocolError {
+        ProtocolError { line: self.number, field, text, kind }
+    }
+}
+
+pub fn read_initial<R: BufRead>(input: &mut ProtocolReader<R>) -> Result<Layout, ProtocolError> {
     let mut cells = Vec::new();
 
-    let mut input_line = String::new();
-    io::stdin().read_line(&mut input_line).unwrap();
-    let number_of_cells = parse_input!(input_line, i32); // amount of hexagonal cells in this map
-    for _ in 0..number_of_cells as usize {
-        let mut input_line = String::new();
-        io::stdin().read_line(&mut input_line).unwrap();
-        let inputs = input_line.split(" ").collect::<Vec<_>>();
+    let number_of_cells = input.next_line("number of cells")?.next::<usize>("number of cells")?; // amount of hexagonal cells in this map
+    for _ in 0..number_of_cells {
+        let mut line = input.next_line("cell")?;
 
-        let contents = match parse_input!(inputs[0], i32) {
+        let contents = match line.next::<i32>("cell type")? {
             0 => None,
             1 => Some(Content::Eggs),
             2 => Some(Content::Crystals),
-            wrong => panic!("Invalid cell type: {}", wrong),
+            wrong => return Err(line.error("cell type", wrong.to_string(), ProtocolErrorKind::InvalidValue)),
         }; // 0 for empty, 1 for eggs, 2 for crystal
 
-        let initial_resources = parse_input!(inputs[1], i32); // the initial amount of eggs/crystals on this cell
+        let initial_resources = line.next::<i32>("initial resources")?; // the initial amount of eggs/crystals on this cell
 
-        let mut neighbors = Vec::new();
-        for i in 0..6 {
-            let neighbor = parse_input!(inputs[2+i], i32); // the index of the neighbouring cell for each direction
-            if neighbor >= 0 {
-                neighbors.push(neighbor as usize);
+        let mut directions = [None; NUM_DIRECTIONS];
+        for direction in directions.iter_mut() {
+            let neighbor = line.next::<i32>("neighbor")?; // the index of the neighbouring cell for each direction
+            if neighbor as i64 >= number_of_cells as i64 {
+                return Err(line.error("neighbor", neighbor.to_string(), ProtocolErrorKind::InvalidValue));
+            } else if neighbor >= 0 {
+                *direction = Some(neighbor as usize);
             }
         }
+        line.expect_end()?;
 
-        cells.push(CellLayout {
-            content: contents,
-            neighbors: neighbors.into_boxed_slice(),
-            initial_resources,
-        });
+        cells.push(CellLayout::new(contents, initial_resources, directions));
     }
-    let mut input_line = String::new();
-    io::stdin().read_line(&mut input_line).unwrap();
 
-    let _number_of_bases = parse_input!(input_line, i32);
+    let number_of_bases = input.next_line("number of bases")?.next::<usize>("number of bases")?;
+    let my_bases = read_bases(input, "my bases", "my base index", number_of_bases, cells.len())?;
+    let enemy_bases = read_bases(input, "enemy bases", "enemy base index", number_of_bases, cells.len())?;
 
-    let mut my_bases = Vec::new();
-    let mut inputs = String::new();
-    io::stdin().read_line(&mut inputs).unwrap();
-    for i in inputs.split_whitespace() {
-        let my_base_index = parse_input!(i, usize);
-        my_bases.push(my_base_index);
-    }
+    Ok(Layout {
+        cells: cells.into_boxed_slice(),
+        bases: [my_bases, enemy_bases],
+    })
+}
 
-    let mut enemy_bases = Vec::new();
-    let mut inputs = String::new();
-    io::stdin().read_line(&mut inputs).unwrap();
-    for i in inputs.split_whitespace() {
-        let opp_base_index = parse_input!(i, usize);
-        enemy_bases.push(opp_base_index);
+/// A line of exactly number_of_bases cell indices
+fn read_bases<R: BufRead>(input: &mut ProtocolReader<R>, line_field: &'static str, field: &'static str, number_of_bases: usize, num_cells: usize) -> Result<Box<[usize]>, ProtocolError> {
+    let mut line = input.next_line(line_field)?;
+    let mut bases = Vec::with_capacity(number_of_bases);
+    for _ in 0..number_of_bases {
+        let base = line.next::<usize>(field)?;
+        if base >= num_cells {
+            return Err(line.error(field, base.to_string(), ProtocolErrorKind::InvalidValue));
+        }
+        bases.push(base);
     }
-
-    let layout = Layout {
-        cells: cells.into_boxed_slice(),
-        bases: [my_bases.into_boxed_slice(), enemy_bases.into_boxed_slice()],
-    };
-    layout
+    line.expect_end()?;
+    Ok(bases.into_boxed_slice())
 }
 
-pub fn read_turn(layout: &Layout) -> TurnInput {
+pub fn read_turn<R: BufRead>(input: &mut ProtocolReader<R>, layout: &Layout) -> Result<TurnInput, ProtocolError> {
     let mut resources_per_cell = Vec::with_capacity(layout.cells.len());
     let mut num_my_ants_per_cell = Vec::with_capacity(layout.cells.len());
     let mut num_enemy_ants_per_cell = Vec::with_capacity(layout.cells.len());
 
     let crystals_per_player = {
-        let mut input_line = String::new();
-        io::stdin().read_line(&mut input_line).unwrap();
-        let inputs = input_line.split(" ").collect::<Vec<_>>();
-        [
-            parse_input!(inputs[0], i32),
-            parse_input!(inputs[1], i32),
-        ]
+        let mut line = input.next_line("scores")?;
+        let crystals_per_player = [
+            line.next::<i32>("my score")?,
+            line.next::<i32>("enemy score")?,
+        ];
+        line.expect_end()?;
+        crystals_per_player
     };
 
     for _ in 0..layout.cells.len() {
-        let mut input_line = String::new();
-        io::stdin().read_line(&mut input_line).unwrap();
-        let inputs = input_line.split(" ").collect::<Vec<_>>();
+        let mut line = input.next_line("cell state")?;
 
-        resources_per_cell.push(parse_input!(inputs[0], i32)); // the current amount of eggs/crystals on this cell
-        num_my_ants_per_cell.push(parse_input!(inputs[1], i32)); // the amount of your ants on this cell
-        num_enemy_ants_per_cell.push(parse_input!(inputs[2], i32)); // the amount of opponent ants on this cell
+        resources_per_cell.push(line.next::<i32>("resources")?); // the current amount of eggs/crystals on this cell
+        num_my_ants_per_cell.push(line.next::<i32>("my ants")?); // the amount of your ants on this cell
+        num_enemy_ants_per_cell.push(line.next::<i32>("enemy ants")?); // the amount of opponent ants on this cell
+        line.expect_end()?;
     }
 
-    TurnInput {
+    Ok(TurnInput {
         crystals_per_player,
         resources_per_cell: resources_per_cell.into_boxed_slice(),
         num_ants_per_cell: [
             num_my_ants_per_cell.into_boxed_slice(),
             num_enemy_ants_per_cell.into_boxed_slice(),
         ],
+    })
+}
+
+/// The inverse of read_initial, written from the given player's perspective
+#[allow(dead_code)]
+pub fn write_initial(layout: &Layout, player: usize, output: &mut impl Write) -> io::Result<()> {
+    writeln!(output, "{}", layout.cells.len())?;
+    for cell in layout.cells.iter() {
+        let cell_type = match cell.content {
+            None => 0,
+            Some(Content::Eggs) => 1,
+            Some(Content::Crystals) => 2,
+        };
+        write!(output, "{} {}", cell_type, cell.initial_resources)?;
+        for neighbor in cell.directions.iter() {
+            match neighbor {
+                Some(neighbor) => write!(output, " {}", neighbor)?,
+                None => write!(output, " -1")?,
+            }
+        }
+        writeln!(output)?;
     }
+
+    let opponent = (player + 1) % NUM_PLAYERS;
+    writeln!(output, "{}", layout.bases[player].len())?;
+    for bases in [&layout.bases[player], &layout.bases[opponent]] {
+        let bases: Vec<String> = bases.iter().map(|base| base.to_string()).collect();
+        writeln!(output, "{}", bases.join(" "))?;
+    }
+    Ok(())
+}
+
+/// The inverse of read_turn, written from the given player's perspective
+#[allow(dead_code)]
+pub fn write_turn(state: &State, player: usize, output: &mut impl Write) -> io::Result<()> {
+    let opponent = (player + 1) % NUM_PLAYERS;
+    writeln!(output, "{} {}", state.crystals[player], state.crystals[opponent])?;
+    for cell in 0..state.resources.len() {
+        writeln!(output, "{} {} {}", state.resources[cell], state.num_ants[player][cell], state.num_ants[opponent][cell])?;
+    }
+    Ok(())
 }
 
 pub fn format_action(action: &Action) -> String {
@@ -113,6 +258,217 @@ pub fn format_action(action: &Action) -> String {
         Action::Beacon { index, strength } => format!("BEACON {} {}", index, strength),
         Action::Line { source, target, strength } => format!("LINE {} {} {}", source, target, strength),
         Action::Message { text } => format!("MESSAGE {}", text),
-        Action::Wait => format!("WAIT"),
+        Action::Wait => "WAIT".to_string(),
+    }
+}
+
+/// The inverse of format_actions, reading one turn's worth of actions from a bot's output
+#[allow(dead_code)]
+pub fn read_actions<R: BufRead>(input: &mut ProtocolReader<R>, layout: &Layout) -> Result<Vec<Action>, ProtocolError> {
+    let line = input.next_line("actions")?;
+    parse_actions(line.number, &line.text, layout)
+}
+
+#[allow(dead_code)]
+pub fn parse_actions(line_number: usize, text: &str, layout: &Layout) -> Result<Vec<Action>, ProtocolError> {
+    let mut actions = Vec::new();
+    for command in text.split(';') {
+        let command = command.trim();
+        if command.is_empty() { continue }
+
+        let mut line = Line::new(line_number, command.to_string());
+        let keyword = line.next::<String>("command")?;
+        let action = match keyword.to_uppercase().as_str() {
+            "BEACON" => {
+                let index = line.next_cell("beacon index", layout)?;
+                let strength = line.next_strength()?;
+                line.expect_end()?;
+                Action::Beacon { index, strength }
+            },
+            "LINE" => {
+                let source = line.next_cell("line source", layout)?;
+                let target = line.next_cell("line target", layout)?;
+                let strength = line.next_strength()?;
+                line.expect_end()?;
+                Action::Line { source, target, strength }
+            },
+            "WAIT" => {
+                line.expect_end()?;
+                Action::Wait
+            },
+            "MESSAGE" => {
+                let text = command[keyword.len()..].trim().to_string();
+                Action::Message { text }
+            },
+            _ => return Err(line.error("command", keyword, ProtocolErrorKind::InvalidValue)),
+        };
+        actions.push(action);
+    }
+    Ok(actions)
+}
+
+/// Formats a whole turn's worth of actions as a single output line
+pub fn format_actions(actions: &[Action]) -> String {
+    if actions.is_empty() {
+        format_action(&Action::Wait)
+    } else {
+        actions.iter().map(format_action).collect::<Vec<_>>().join(";")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use super::*;
+    use crate::mapgen;
+
+    fn read_initial_from(text: &[u8]) -> Layout {
+        read_initial(&mut ProtocolReader::new(text)).expect("invalid initial input")
+    }
+
+    #[test]
+    fn initial_round_trip() {
+        for seed in 0..20 {
+            let (layout, _) = mapgen::generate(&mut StdRng::seed_from_u64(seed));
+
+            let mut output = Vec::new();
+            write_initial(&layout, ME, &mut output).unwrap();
+            assert_eq!(read_initial_from(&output), layout);
+        }
+    }
+
+    #[test]
+    fn initial_round_trip_swaps_bases_for_enemy() {
+        for seed in 0..20 {
+            let (layout, _) = mapgen::generate(&mut StdRng::seed_from_u64(seed));
+
+            let mut output = Vec::new();
+            write_initial(&layout, ENEMY, &mut output).unwrap();
+            let read = read_initial_from(&output);
+
+            assert_eq!(read.cells, layout.cells);
+            assert_eq!(read.bases[ME], layout.bases[ENEMY]);
+            assert_eq!(read.bases[ENEMY], layout.bases[ME]);
+        }
+    }
+
+    #[test]
+    fn base_lines_must_match_number_of_bases() {
+        let read = |bases: &str| read_initial(&mut ProtocolReader::new(format!("2\n0 0 1 -1 -1 -1 -1 -1\n0 0 -1 -1 -1 0 -1 -1\n{}", bases).as_bytes()));
+
+        assert!(read("1\n0\n1\n").is_ok());
+
+        let error = read("2\n0\n1\n").unwrap_err();
+        assert!(matches!(error.kind, ProtocolErrorKind::MissingField));
+        assert_eq!((error.line, error.field), (5, "my base index"));
+
+        let error = read("1\n0\n1 0\n").unwrap_err();
+        assert!(matches!(error.kind, ProtocolErrorKind::InvalidValue));
+        assert_eq!((error.line, error.field, error.text.as_str()), (6, "trailing argument", "0"));
+
+        let error = read("1\n0\n2\n").unwrap_err();
+        assert!(matches!(error.kind, ProtocolErrorKind::InvalidValue));
+        assert_eq!((error.line, error.field, error.text.as_str()), (6, "enemy base index", "2"));
+    }
+
+    #[test]
+    fn neighbors_must_be_cells() {
+        let error = read_initial(&mut ProtocolReader::new(&b"2\n0 0 7 -1 -1 -1 -1 -1\n0 0 -1 -1 -1 0 -1 -1\n1\n0\n1\n"[..])).unwrap_err();
+        assert!(matches!(error.kind, ProtocolErrorKind::InvalidValue));
+        assert_eq!((error.line, error.field, error.text.as_str()), (2, "neighbor", "7"));
+
+        let error = read_initial(&mut ProtocolReader::new(&b"2\n0 0 1 -1 -1 -1 -1 -1 9\n0 0 -1 -1 -1 0 -1 -1\n1\n0\n1\n"[..])).unwrap_err();
+        assert_eq!((error.line, error.field, error.text.as_str()), (2, "trailing argument", "9"));
+    }
+
+    #[test]
+    fn turn_lines_reject_trailing_arguments() {
+        let layout = read_initial_from(b"2\n0 0 1 -1 -1 -1 -1 -1\n0 0 -1 -1 -1 0 -1 -1\n1\n0\n1\n");
+        assert!(read_turn(&mut ProtocolReader::new(&b"0 0\n0 5 0\n0 0 5\n"[..]), &layout).is_ok());
+
+        let error = read_turn(&mut ProtocolReader::new(&b"0 0\n0 5 0 9 9\n0 0 5\n"[..]), &layout).err().expect("trailing cell state accepted");
+        assert_eq!((error.line, error.field, error.text.as_str()), (2, "trailing argument", "9"));
+
+        let error = read_turn(&mut ProtocolReader::new(&b"0 0 1\n0 5 0\n0 0 5\n"[..]), &layout).err().expect("trailing score accepted");
+        assert_eq!((error.line, error.field, error.text.as_str()), (1, "trailing argument", "1"));
+    }
+
+    #[test]
+    fn turn_round_trip() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for seed in 0..20 {
+            let (layout, _) = mapgen::generate(&mut StdRng::seed_from_u64(seed));
+            let num_cells = layout.cells.len();
+            let state = State::new(
+                0,
+                [
+                    (0..num_cells).map(|_| rng.gen_range(0..20)).collect(),
+                    (0..num_cells).map(|_| rng.gen_range(0..20)).collect(),
+                ],
+                (0..num_cells).map(|_| rng.gen_range(0..100)).collect(),
+                [rng.gen_range(0..100), rng.gen_range(0..100)]);
+
+            for player in 0..NUM_PLAYERS {
+                let opponent = (player + 1) % NUM_PLAYERS;
+
+                let mut output = Vec::new();
+                write_turn(&state, player, &mut output).unwrap();
+                let turn = read_turn(&mut ProtocolReader::new(output.as_slice()), &layout).expect("invalid turn input");
+
+                assert_eq!(turn.crystals_per_player, [state.crystals[player], state.crystals[opponent]]);
+                assert_eq!(turn.resources_per_cell, state.resources);
+                assert_eq!(turn.num_ants_per_cell[ME], state.num_ants[player]);
+                assert_eq!(turn.num_ants_per_cell[ENEMY], state.num_ants[opponent]);
+            }
+        }
+    }
+
+    #[test]
+    fn actions_round_trip() {
+        let (layout, _) = mapgen::generate(&mut StdRng::seed_from_u64(0));
+        let last = layout.cells.len() - 1;
+        let actions = vec![
+            Action::Beacon { index: 0, strength: 3 },
+            Action::Line { source: 1, target: last, strength: 1 },
+            Action::Message { text: "hello there world".to_string() },
+            Action::Wait,
+        ];
+
+        let text = format_actions(&actions);
+        assert_eq!(read_actions(&mut ProtocolReader::new(format!("{}\n", text).as_bytes()), &layout).unwrap(), actions);
+        assert_eq!(format_actions(&[]), "WAIT");
+    }
+
+    #[test]
+    fn invalid_actions_are_rejected() {
+        let (layout, _) = mapgen::generate(&mut StdRng::seed_from_u64(0));
+        let num_cells = layout.cells.len();
+        let error = |text: &str| parse_actions(1, text, &layout).unwrap_err();
+
+        let out_of_range = error(&format!("BEACON {} 1", num_cells));
+        assert!(matches!(out_of_range.kind, ProtocolErrorKind::InvalidValue));
+        assert_eq!((out_of_range.field, out_of_range.text), ("beacon index", num_cells.to_string()));
+
+        let out_of_range = error(&format!("WAIT;LINE 0 {} 1", num_cells));
+        assert_eq!((out_of_range.field, out_of_range.text), ("line target", num_cells.to_string()));
+
+        for strength in ["0", "-2"] {
+            let weak = error(&format!("BEACON 0 {}", strength));
+            assert!(matches!(weak.kind, ProtocolErrorKind::InvalidValue));
+            assert_eq!((weak.field, weak.text.as_str()), ("strength", strength));
+        }
+
+        let unknown = error("BEACON 0 1;JUMP 3");
+        assert!(matches!(unknown.kind, ProtocolErrorKind::InvalidValue));
+        assert_eq!((unknown.field, unknown.text.as_str()), ("command", "JUMP"));
+
+        let trailing = error("LINE 0 1 2 3");
+        assert!(matches!(trailing.kind, ProtocolErrorKind::InvalidValue));
+        assert_eq!((trailing.field, trailing.text.as_str()), ("trailing argument", "3"));
+
+        let missing = error("BEACON 0");
+        assert!(matches!(missing.kind, ProtocolErrorKind::MissingField));
+        assert_eq!(missing.field, "strength");
     }
 }
@@ -0,0 +1,20 @@
+pub mod agent;
+pub mod desync;
+pub mod evaluation;
+pub mod fnv;
+pub mod interface;
+pub mod harvesting;
+pub mod hex;
+pub mod inputs;
+pub mod mapgen;
+pub mod movement;
+pub mod pathing;
+pub mod planning;
+pub mod recording;
+pub mod simulation;
+pub mod transportation;
+pub mod solving;
+pub mod validation;
+pub mod valuation;
+pub mod view;
+pub mod zobrist;
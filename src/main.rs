@@ -1,32 +1,73 @@
+// The bot declares its own copy of the module tree rather than using the library,
+// so that cargo merge can inline everything into the single file that CodinGame needs.
+// Modules only the tools use allow dead code here, and items in shared modules that only the tools use allow it individually.
+
 mod agent;
+mod desync;
 mod evaluation;
 mod fnv;
 mod interface;
 mod harvesting;
+#[allow(dead_code)] mod hex;
 mod inputs;
+#[allow(dead_code)] mod mapgen;
 mod movement;
 mod pathing;
 mod planning;
+mod recording;
 mod simulation;
+mod transportation;
 mod solving;
+mod validation;
 mod valuation;
 mod view;
+#[allow(dead_code)] mod zobrist;
 
-use agent::Agent;
-use inputs::*;
-use view::*;
+use std::io;
 
-use interface::TurnInput;
+use agent::{Agent,AgentConfig};
+use interface::{ProtocolError,ProtocolReader,TurnInput};
+use recording::RecordingReader;
+use view::*;
 
 fn main() {
-    let layout = interface::read_initial();
+    // Usage: spring-challenge-2023 [--record <replay file>] [--iterations <solver steps per tick>]
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut config = AgentConfig::default();
+    if let Some(index) = args.iter().position(|arg| arg == "--iterations") {
+        let iterations = args.get(index + 1).and_then(|value| value.parse().ok()).expect("--iterations requires a number");
+        config.iterations = Some(iterations);
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--record") {
+        let path = args.get(index + 1).expect("--record requires a path");
+        if let Err(error) = recording::start(path) {
+            eprintln!("Unable to record to {}: {}", path, error);
+        }
+    }
+
+    // The replay needs these to make the same choices again
+    recording::record_config("seed", &config.seed.to_string());
+    if let Some(iterations) = config.iterations {
+        recording::record_config("iterations", &iterations.to_string());
+    }
+
+    if let Err(error) = run(config) {
+        diagnostic!("Protocol error: {}", error);
+        recording::flush();
+        std::process::exit(1);
+    }
+}
+
+fn run(config: AgentConfig) -> Result<(), ProtocolError> {
+    let mut input = ProtocolReader::new(RecordingReader::new(io::stdin().lock()));
+    let layout = interface::read_initial(&mut input)?;
     let view = View::new(layout);
 
-    let mut agent = Agent::new(&view);
+    let mut agent = Agent::with_config(&view, config);
     let mut tick = 0;
     loop {
         // Read input
-        let TurnInput { crystals_per_player, num_ants_per_cell, resources_per_cell } = interface::read_turn(&view.layout);
+        let TurnInput { crystals_per_player, num_ants_per_cell, resources_per_cell } = interface::read_turn(&mut input, &view.layout)?;
 
         // Calculate new state
         let state = State::new(tick, num_ants_per_cell, resources_per_cell, crystals_per_player);
@@ -35,18 +76,11 @@ fn main() {
         let actions = agent.act(&view, &state);
 
         // Emit actions
-        if actions.is_empty() {
-            println!("{}", interface::format_action(&Action::Wait));
-        } else {
-            for (i,action) in actions.into_iter().enumerate() {
-                if i > 0 {
-                    print!(";");
-                }
-                print!("{}", interface::format_action(&action));
-            }
-            println!("");
-        }
+        let output = interface::format_actions(&actions);
+        println!("{}", output);
+        recording::record_output(&output);
+        recording::flush();
 
         tick += 1;
     }
-}
\ No newline at end of file
+}
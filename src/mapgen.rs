@@ -0,0 +1,157 @@
+use rand::prelude::*;
+use super::fnv::FnvHashMap;
+
+use super::hex::Hex;
+use super::inputs::*;
+use super::view::*;
+
+const MIN_RADIUS: i32 = 4;
+const MAX_RADIUS: i32 = 7;
+const MAX_HOLE_PROBABILITY: f64 = 0.25;
+const MAX_BASES: usize = 2;
+
+const EGGS_PROBABILITY: f64 = 0.1;
+const CRYSTALS_PROBABILITY: f64 = 0.1;
+const MIN_EGGS: i32 = 10;
+const MAX_EGGS: i32 = 40;
+const MIN_CRYSTALS: i32 = 20;
+const MAX_CRYSTALS: i32 = 120;
+
+const MIN_ANTS_PER_BASE: i32 = 10;
+const MAX_ANTS_PER_BASE: i32 = 20;
+
+/// Generates a point-symmetric hex map in the style of the contest maps, along with its starting state.
+/// Like the contest maps, cell 0 is the centre and every other cell 2k+1 is mirrored by cell 2k+2.
+/// Both players get the same bases, resources and ants, mirrored.
+pub fn generate(rng: &mut StdRng) -> (Layout,State) {
+    let hexes = generate_hexes(rng);
+    let lookup: FnvHashMap<Hex, usize> = hexes.iter().enumerate().map(|(cell, &hex)| (hex, cell)).collect();
+    let num_cells = hexes.len();
+    let num_pairs = (num_cells - 1) / 2;
+    let radius = hexes.iter().map(|hex| hex.length()).max().unwrap_or(0);
+
+    // Bases go on the outer half of the map
+    let mut candidates: Vec<usize> = (0..num_pairs).filter(|&pair| hexes[pair * 2 + 1].length() * 2 >= radius).collect();
+    if candidates.is_empty() {
+        candidates = (0..num_pairs).collect();
+    }
+    candidates.shuffle(rng);
+    let num_bases = rng.gen_range(1..=MAX_BASES).min(candidates.len());
+    let base_pairs = &candidates[0..num_bases];
+
+    let mut contents = vec![None; num_cells];
+    let mut resources = vec![0; num_cells];
+    let mut place = |cell: usize, content: Content, amount: i32| {
+        for mirrored in [cell, mirror_of(cell)] {
+            contents[mirrored] = Some(content);
+            resources[mirrored] = amount;
+        }
+    };
+
+    // Every base gets some eggs nearby so both players can grow from the start
+    let mut reserved: Vec<usize> = base_pairs.iter().flat_map(|&pair| [pair * 2 + 1, pair * 2 + 2]).collect();
+    for &pair in base_pairs.iter() {
+        let base = pair * 2 + 1;
+        let neighbors: Vec<usize> = (0..NUM_DIRECTIONS)
+            .filter_map(|direction| lookup.get(&hexes[base].neighbor(direction)).cloned())
+            .filter(|&cell| cell != 0 && !reserved.contains(&cell))
+            .collect();
+        if let Some(&cell) = neighbors.choose(rng) {
+            place(cell, Content::Eggs, rng.gen_range(MIN_EGGS..=MAX_EGGS));
+            reserved.extend([cell, mirror_of(cell)]);
+        }
+    }
+
+    let mut has_crystals = false;
+    for cell in std::iter::once(0).chain((1..num_cells).step_by(2)) { // The centre, then the first cell of each mirrored pair
+        if reserved.contains(&cell) { continue }
+
+        let roll = rng.gen::<f64>();
+        if roll < CRYSTALS_PROBABILITY {
+            place(cell, Content::Crystals, rng.gen_range(MIN_CRYSTALS..=MAX_CRYSTALS));
+            has_crystals = true;
+        } else if roll < CRYSTALS_PROBABILITY + EGGS_PROBABILITY {
+            place(cell, Content::Eggs, rng.gen_range(MIN_EGGS..=MAX_EGGS));
+        }
+    }
+    if !has_crystals {
+        place(0, Content::Crystals, rng.gen_range(MIN_CRYSTALS..=MAX_CRYSTALS));
+    }
+
+    let cells: Vec<CellLayout> = hexes.iter().enumerate().map(|(cell, hex)| {
+        let mut directions = [None; NUM_DIRECTIONS];
+        for (direction, neighbor) in directions.iter_mut().enumerate() {
+            *neighbor = lookup.get(&hex.neighbor(direction)).cloned();
+        }
+        CellLayout::new(contents[cell], resources[cell], directions)
+    }).collect();
+
+    let layout = Layout {
+        cells: cells.into_boxed_slice(),
+        bases: [
+            base_pairs.iter().map(|&pair| pair * 2 + 1).collect(),
+            base_pairs.iter().map(|&pair| pair * 2 + 2).collect(),
+        ],
+    };
+
+    let ants_per_base = rng.gen_range(MIN_ANTS_PER_BASE..=MAX_ANTS_PER_BASE);
+    let mut num_ants = [vec![0; num_cells].into_boxed_slice(), vec![0; num_cells].into_boxed_slice()];
+    for (ants, bases) in num_ants.iter_mut().zip(layout.bases.iter()) {
+        for &base in bases.iter() {
+            ants[base] = ants_per_base;
+        }
+    }
+    let state = State::new(0, num_ants, resources.into_boxed_slice(), [0; NUM_PLAYERS]);
+
+    (layout, state)
+}
+
+/// Cell indices for the generated map: the centre first, then mirrored pairs moving outwards ring by ring
+fn generate_hexes(rng: &mut StdRng) -> Vec<Hex> {
+    let radius = rng.gen_range(MIN_RADIUS..=MAX_RADIUS);
+    let hole_probability = rng.gen_range(0.0..MAX_HOLE_PROBABILITY);
+
+    let center = Hex::default();
+    let mut candidates = vec![center];
+    let mut visited = Vec::new();
+    for ring in 1..=radius {
+        for hex in center.ring(ring) {
+            if visited.contains(&hex) { continue } // Already considered as part of a pair
+            visited.extend([hex, hex.mirror()]);
+
+            if rng.gen::<f64>() < hole_probability { continue }
+            candidates.push(hex);
+            candidates.push(hex.mirror());
+        }
+    }
+
+    // Holes can cut off parts of the map, so only keep what is reachable from the centre.
+    // The map is symmetric so whatever is reachable is also symmetric.
+    let mut reachable = vec![false; candidates.len()];
+    reachable[0] = true;
+    let mut stack = vec![center];
+    while let Some(hex) = stack.pop() {
+        for direction in 0..NUM_DIRECTIONS {
+            let neighbor = hex.neighbor(direction);
+            if let Some(index) = candidates.iter().position(|&h| h == neighbor) {
+                if !reachable[index] {
+                    reachable[index] = true;
+                    stack.push(neighbor);
+                }
+            }
+        }
+    }
+
+    let hexes: Vec<Hex> = candidates.into_iter().zip(reachable).filter_map(|(hex, reachable)| if reachable { Some(hex) } else { None }).collect();
+    if hexes.len() < 3 {
+        // Too many holes to place a base for each player, try again
+        return generate_hexes(rng);
+    }
+    hexes
+}
+
+fn mirror_of(cell: usize) -> usize {
+    if cell == 0 { 0 }
+    else if cell % 2 == 1 { cell + 1 }
+    else { cell - 1 }
+}
@@ -1,11 +1,14 @@
-use super::fnv::FnvHashSet;
-
 use super::inputs::*;
+use super::simulation::{Event,EventLog};
+use super::transportation;
 use super::view::*;
 
 pub type Assignments = Box<[i32]>;
 pub type AssignmentsPerPlayer = [Assignments; NUM_PLAYERS];
 
+/// Shorter chains are not worth expressing as a LINE
+const MIN_LINE_LENGTH: i32 = 3;
+
 struct Movement {
     pub source: usize,
     pub sink: usize,
@@ -19,32 +22,57 @@ struct Candidate {
     pub sink: usize,
 }
 
-pub fn spread_ants_across_beacons(beacons: impl Iterator<Item=usize>, player: usize, view: &View, state: &State) -> Assignments {
+/// How ants move towards the beacons they have been assigned
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum MovementModel {
+    /// Assignments are the exact number of ants wanted on each cell, and ants move to fill them with the least total travel.
+    /// This is what our plans assume and is what rollouts use.
+    MinCost,
+
+    /// Assignments are beacon strengths, and ants are allocated to beacons the way the official referee does it
+    #[allow(dead_code)]
+    Referee,
+}
+
+/// Divides the player's ants between the beacons in proportion to each beacon's weight
+pub fn spread_ants_across_beacons(beacons: impl Iterator<Item=(usize,i32)>, player: usize, view: &View, state: &State) -> Assignments {
     let num_cells = state.resources.len();
-    let mut beacons: Vec<usize> = beacons.collect();
-    beacons.sort_by_key(|&cell| (view.distance_to_closest_base[player][cell], cell));
+    let mut beacons: Vec<(usize,i32)> = beacons.filter(|&(_, weight)| weight > 0).collect();
+    beacons.sort_by_key(|&(cell, _)| (view.distance_to_closest_base[player][cell], cell));
 
     let mut assignments = Vec::new();
     assignments.resize(num_cells, 0);
 
-    let mut remaining_beacons = beacons.len() as i32;
-    let mut remaining_ants = state.total_ants[player];
-    for cell in beacons.into_iter() { // Place ants from closest to farthest beacon because rounding will cause more ants to be placed further away
+    let mut remaining_weight = beacons.iter().map(|&(_, weight)| weight as i64).sum::<i64>();
+    let mut remaining_ants = state.total_ants[player] as i64;
+    for (cell, weight) in beacons.into_iter() { // Place ants from closest to farthest beacon because rounding will cause more ants to be placed further away
         if remaining_ants <= 0 { break }
 
-        let assign_to_this_beacon = remaining_ants / remaining_beacons;
+        let assign_to_this_beacon = remaining_ants * weight as i64 / remaining_weight;
 
-        remaining_beacons -= 1;
+        remaining_weight -= weight as i64;
         remaining_ants -= assign_to_this_beacon;
-        assignments[cell] = assign_to_this_beacon;
+        assignments[cell] = assign_to_this_beacon as i32;
     }
 
     assignments.into_boxed_slice()
 }
 
-pub fn assignments_to_actions(assignments: &[i32]) -> Vec<Action> {
+/// Express the assignments as beacons. If use_lines is set, chains of equal-strength beacons along a shortest path become a single LINE.
+/// num_ants is the player's ants, which decide the path the referee will expand each LINE along.
+pub fn assignments_to_actions(assignments: &[i32], use_lines: bool, num_ants: &[i32], view: &View) -> Vec<Action> {
     let mut actions = Vec::new();
-    for (cell, &num_ants) in assignments.iter().enumerate() {
+    let mut remaining = assignments.to_vec();
+    if use_lines {
+        while let Some((source, target, strength)) = find_longest_line(&remaining, num_ants, view) {
+            for cell in line_path(source, target, num_ants, view) {
+                remaining[cell] = 0;
+            }
+            actions.push(Action::Line { source, target, strength });
+        }
+    }
+
+    for (cell, &num_ants) in remaining.iter().enumerate() {
         if num_ants > 0 {
             actions.push(Action::Beacon {
                 index: cell,
@@ -55,70 +83,245 @@ pub fn assignments_to_actions(assignments: &[i32]) -> Vec<Action> {
     actions
 }
 
-pub fn move_ants_for_player(assignments: &Assignments, view: &View, num_ants: &mut AntsPerCell) {
-    let num_cells = view.layout.cells.len();
+/// Finds the longest shortest path whose cells all have the same strength, so they can be placed with a single LINE
+fn find_longest_line(remaining: &[i32], num_ants: &[i32], view: &View) -> Option<(usize, usize, i32)> {
+    let beacons: Vec<usize> = (0..remaining.len()).filter(|&cell| remaining[cell] > 0).collect();
 
-    // Calculate sources and sinks
-    let mut excess: Vec<i32> = Vec::with_capacity(num_cells);
-    let mut sources: FnvHashSet<usize> = FnvHashSet::default();
-    let mut sinks: FnvHashSet<usize> = FnvHashSet::default();
-    for cell in 0..num_cells {
-        let cell_excess = num_ants[cell] - assignments[cell];
-        if cell_excess > 0 {
-            sources.insert(cell);
-        } else if cell_excess < 0 {
-            sinks.insert(cell);
+    let mut best = None;
+    let mut best_length = MIN_LINE_LENGTH - 1;
+    for &source in beacons.iter() {
+        let strength = remaining[source];
+        for &target in beacons.iter() {
+            if remaining[target] != strength { continue }
+
+            let length = view.paths.distance_between(source, target) + 1;
+            if length <= best_length { continue }
+
+            if line_path(source, target, num_ants, view).into_iter().all(|cell| remaining[cell] == strength) {
+                best = Some((source, target, strength));
+                best_length = length;
+            }
         }
-        excess.push(cell_excess);
     }
+    best
+}
 
-    // Assign which ants should move where
-    let mut movements = Vec::new();
-    while !sources.is_empty() && !sinks.is_empty() {
-        let closest =
-            sinks.iter().map(|sink| {
-                sources.iter().map(|source| {
-                    Candidate {
-                        source: *source,
-                        sink: *sink,
-                        distance: view.paths.distance_between(*source, *sink),
-                    }
-                }).min().expect("no sources")
-            }).min().expect("no sinks");
-
-        let available = excess[closest.source];
-        let required = -excess[closest.sink];
-        let assigned = available.min(required);
-        if assigned <= 0 { panic!("Nothing to assign from source to sink") }
-
-        movements.push(Movement {
-            source: closest.source,
-            sink: closest.sink,
-            assigned,
-        });
-
-        excess[closest.source] -= assigned;
-        if excess[closest.source] <= 0 {
-            sources.remove(&closest.source);
+/// The beacon strength placed on each cell by the given player's actions, with lines expanded into beacons along their path
+#[allow(dead_code)]
+pub fn actions_to_beacons(player: usize, actions: &[Action], view: &View, state: &State) -> Assignments {
+    let mut strengths = vec![0; view.layout.cells.len()];
+    for action in actions.iter() {
+        match action {
+            &Action::Beacon { index, strength } => strengths[index] += strength,
+            &Action::Line { source, target, strength } => {
+                for cell in line_path(source, target, &state.num_ants[player], view) {
+                    strengths[cell] += strength;
+                }
+            },
+            Action::Message { .. } | Action::Wait => (),
         }
+    }
+    strengths.into_boxed_slice()
+}
 
-        excess[closest.sink] += assigned;
-        if excess[closest.sink] >= 0 {
-            sinks.remove(&closest.sink);
-        }
+/// The cells a LINE from source to target covers, inclusive of both ends, stepping the same way as the referee moves ants
+pub fn line_path(source: usize, target: usize, num_ants: &[i32], view: &View) -> Vec<usize> {
+    let mut path = vec![source];
+    let mut cell = source;
+    while cell != target {
+        cell = step_like_referee(cell, target, num_ants, view).expect("Unable to find a path from source to sink");
+        path.push(cell);
+    }
+    path
+}
+
+/// The referee's next step from source towards sink. Of the neighbors on a shortest path, it takes the one containing the most of the player's ants,
+/// then the one with the lowest index. Returns None if source is the sink or the sink cannot be reached.
+fn step_like_referee(source: usize, sink: usize, num_ants: &[i32], view: &View) -> Option<usize> {
+    let distance_to_sink = view.paths.distance_between(source, sink);
+    view.layout.cells[source].neighbors.iter().cloned()
+        .filter(|&n| view.paths.distance_between(n, sink) < distance_to_sink)
+        .max_by_key(|&n| (num_ants[n], std::cmp::Reverse(n)))
+}
+
+pub fn move_ants_for_player(player: usize, assignments: &Assignments, model: MovementModel, view: &View, num_ants: &mut AntsPerCell, log: &mut impl EventLog) {
+    match model {
+        MovementModel::MinCost => move_ants_min_cost(player, assignments, view, num_ants, log),
+        MovementModel::Referee => move_ants_like_referee(player, assignments, view, num_ants, log),
     }
+}
+
+/// Pairs up cells with too many ants and cells with too few so the least total distance is travelled, then every ant takes one step
+fn move_ants_min_cost(player: usize, assignments: &Assignments, view: &View, num_ants: &mut AntsPerCell, log: &mut impl EventLog) {
+    let num_cells = view.layout.cells.len();
+
+    let excess: Vec<i32> = (0..num_cells).map(|cell| num_ants[cell] - assignments[cell]).collect();
+    let movements = transportation::solve(&excess, &view.paths).into_iter().map(|shipment| Movement {
+        source: shipment.source,
+        sink: shipment.sink,
+        assigned: shipment.amount,
+    });
 
     // Perform movement
     for movement in movements {
-        if let Some(next) = view.paths.step_towards(movement.source, movement.sink, &view.layout) {
+        if let Some(next) = view.paths.step_towards(movement.source, movement.sink) {
             let source_ants = &mut num_ants[movement.source];
             if movement.assigned > *source_ants { panic!("Not enough ants to move") }
             *source_ants -= movement.assigned;
 
             num_ants[next] += movement.assigned;
+            log.log(Event::Moved { player, source: movement.source, target: next, num_ants: movement.assigned });
 
         } else {
             panic!("Unable to find a path from source to sink")
         }
     }
-}
\ No newline at end of file
+}
+
+/// Follows the official referee: beacon strengths are scaled to the number of ants,
+/// then every (ant cell, beacon) pair is visited from closest to farthest, ties broken by ant cell index then beacon index,
+/// and each pair takes as many ants as the beacon still has room for.
+/// The first pass fills beacons up to their rounded-down share, a second pass lets them take their rounded-up share.
+/// Ants then take one step towards their beacon using step_like_referee.
+fn move_ants_like_referee(player: usize, beacons: &Assignments, view: &View, num_ants: &mut AntsPerCell, log: &mut impl EventLog) {
+    let num_cells = view.layout.cells.len();
+
+    let ant_cells: Vec<usize> = (0..num_cells).filter(|&cell| num_ants[cell] > 0).collect();
+    let beacon_cells: Vec<usize> = (0..num_cells).filter(|&cell| beacons[cell] > 0).collect();
+    if ant_cells.is_empty() || beacon_cells.is_empty() { return }
+
+    let total_ants: i32 = ant_cells.iter().map(|&cell| num_ants[cell]).sum();
+    let total_strength: i64 = beacon_cells.iter().map(|&cell| beacons[cell] as i64).sum();
+    let scaling_factor = total_ants as f64 / total_strength as f64;
+
+    let mut low_targets = vec![0; num_cells];
+    let mut high_targets = vec![0; num_cells];
+    for &cell in beacon_cells.iter() {
+        let target = beacons[cell] as f64 * scaling_factor;
+        low_targets[cell] = target as i32;
+        high_targets[cell] = target.ceil() as i32;
+    }
+
+    let mut pairs = Vec::with_capacity(ant_cells.len() * beacon_cells.len());
+    for &source in ant_cells.iter() {
+        for &sink in beacon_cells.iter() {
+            pairs.push(Candidate {
+                distance: view.paths.distance_between(source, sink),
+                source,
+                sink,
+            });
+        }
+    }
+    pairs.sort();
+
+    let mut remaining = num_ants.clone();
+    let mut allocated = vec![0; num_cells];
+    let mut movements = Vec::new();
+    for targets in [&low_targets, &high_targets] {
+        for pair in pairs.iter() {
+            let available = remaining[pair.source];
+            let wiggle_room = targets[pair.sink] - allocated[pair.sink];
+            if available <= 0 || wiggle_room <= 0 { continue }
+
+            let assigned = available.min(wiggle_room);
+            remaining[pair.source] -= assigned;
+            allocated[pair.sink] += assigned;
+            movements.push(Movement {
+                source: pair.source,
+                sink: pair.sink,
+                assigned,
+            });
+        }
+    }
+
+    // Perform movement, with all paths chosen based on where the ants were before anything moved
+    let initial_ants = num_ants.clone();
+    for movement in movements {
+        if movement.source == movement.sink { continue }
+
+        let next = step_like_referee(movement.source, movement.sink, &initial_ants, view).expect("Unable to find a path from source to sink");
+
+        num_ants[movement.source] -= movement.assigned;
+        num_ants[next] += movement.assigned;
+        log.log(Event::Moved { player, source: movement.source, target: next, num_ants: movement.assigned });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex::Hex;
+
+    /// A layout with a cell at each hex, linked to whichever other hexes are adjacent
+    fn layout_from_hexes(hexes: &[Hex]) -> Layout {
+        let cells: Vec<CellLayout> = hexes.iter().map(|hex| {
+            let mut directions = [None; NUM_DIRECTIONS];
+            for (direction, neighbor) in directions.iter_mut().enumerate() {
+                *neighbor = hexes.iter().position(|&other| other == hex.neighbor(direction));
+            }
+            CellLayout::new(None, 0, directions)
+        }).collect();
+
+        Layout {
+            cells: cells.into_boxed_slice(),
+            bases: [Box::new([0]), Box::new([hexes.len() - 1])],
+        }
+    }
+
+    fn row(num_cells: i32) -> View {
+        View::new(layout_from_hexes(&(0..num_cells).map(|q| Hex::new(q, 0)).collect::<Vec<_>>()))
+    }
+
+    fn move_like_referee(beacons: &[i32], num_ants: &[i32], view: &View) -> Box<[i32]> {
+        let mut num_ants: AntsPerCell = num_ants.into();
+        move_ants_for_player(ME, &beacons.into(), MovementModel::Referee, view, &mut num_ants, &mut ());
+        num_ants
+    }
+
+    #[test]
+    fn referee_splits_by_strength_and_rounds_up_in_pair_order() {
+        // 10 ants split 3:1 is 7.5 and 2.5, so 7 and 2 are allocated first,
+        // then the last ant goes to the first pair with room to round up, which is beacon 1 as it has the lower index
+        let view = row(5);
+        assert_eq!(*move_like_referee(&[0, 3, 0, 1, 0], &[0, 0, 10, 0, 0], &view), [0, 8, 0, 2, 0]);
+        assert_eq!(*move_like_referee(&[0, 1, 0, 3, 0], &[0, 0, 10, 0, 0], &view), [0, 3, 0, 7, 0]);
+    }
+
+    #[test]
+    fn referee_breaks_ties_by_ant_cell_then_beacon() {
+        let view = row(7);
+
+        // Cells 1 and 3 are both next to beacon 2, which has room for only one group, so cell 1 gets it and cell 3 goes to beacon 6
+        assert_eq!(*move_like_referee(&[0, 0, 1, 0, 0, 0, 1], &[0, 2, 0, 2, 0, 0, 0], &view), [0, 0, 2, 0, 2, 0, 0]);
+
+        // Beacons 2 and 4 are equally close and only one can round up, so the lower index wins
+        assert_eq!(*move_like_referee(&[0, 0, 1, 0, 1, 0, 0], &[0, 0, 0, 1, 0, 0, 0], &view), [0, 0, 1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn referee_steps_towards_own_ants() {
+        // Cells 1 and 2 are both on a shortest path from cell 0 to cell 3, and cell 2 already has an ant
+        let view = View::new(layout_from_hexes(&[Hex::new(0, 0), Hex::new(1, 0), Hex::new(1, -1), Hex::new(2, -1)]));
+        assert_eq!(*move_like_referee(&[0, 0, 0, 1], &[2, 0, 1, 0], &view), [0, 0, 2, 1]);
+
+        // With no ants in between, the lower cell index is taken
+        assert_eq!(*move_like_referee(&[0, 0, 0, 1], &[2, 0, 0, 0], &view), [0, 2, 0, 0]);
+    }
+
+    #[test]
+    fn line_expands_along_the_referee_step() {
+        let view = View::new(layout_from_hexes(&[Hex::new(0, 0), Hex::new(1, 0), Hex::new(1, -1), Hex::new(2, -1)]));
+        let line = [Action::Line { source: 0, target: 3, strength: 2 }];
+        let state_with = |num_ants: [i32; 4]| State::new(0, [num_ants.into(), Box::new([0; 4])], Box::new([0; 4]), [0, 0]);
+
+        // The line goes through whichever middle cell has more of the player's ants, otherwise the lower index
+        assert_eq!(*actions_to_beacons(ME, &line, &view, &state_with([0, 0, 1, 0])), [2, 0, 2, 2]);
+        assert_eq!(*actions_to_beacons(ME, &line, &view, &state_with([0, 0, 0, 0])), [2, 2, 0, 2]);
+
+        // Only the player's own ants count
+        assert_eq!(*actions_to_beacons(ENEMY, &line, &view, &state_with([0, 0, 1, 0])), [2, 2, 0, 2]);
+
+        // The agent emits the same line for the cells it meant
+        assert_eq!(assignments_to_actions(&[2, 0, 2, 2], true, &[0, 0, 1, 0], &view), line);
+    }
+}
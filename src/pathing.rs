@@ -1,4 +1,5 @@
-use std::collections::VecDeque;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap,VecDeque};
 use super::fnv::FnvHashMap;
 
 use super::inputs::*;
@@ -66,13 +67,19 @@ impl NearbyPathMap {
     }
 
     pub fn step_towards(&self, source: usize, sink: usize, layout: &Layout, paths: &PathMap) -> Option<usize> {
-        let distances_to_sink = &paths.sources[sink].distances; // The distance map is symmetrical, so can use the sink as a source
+        let distances_to_sink = paths.distances_from(sink); // The distances are symmetrical, so can use the sink as a source
         let best = layout.cells[source].neighbors.iter().min_by_key(|&&n| {
             (distances_to_sink[n], self.distance_to_nearest[n])
         }).cloned();
         best
     }
 
+    /// Like calculate_weighted_path, but ties go to paths closer to the existing ants, the same preference as calculate_path
+    pub fn calculate_weighted_path(&self, source: usize, sink: usize, layout: &Layout, cell_cost: impl Fn(usize) -> i32) -> Option<Vec<usize>> {
+        let num_cells = layout.cells.len() as i32;
+        calculate_weighted_path(source, sink, layout, cell_cost, |cell| self.distance_to_nearest[cell].min(num_cells))
+    }
+
     pub fn calculate_path<'a>(&'a self, source: usize, sink: usize, layout: &'a Layout, paths: &'a PathMap) -> impl Iterator<Item=usize> + 'a {
         let mut next = Some(source);
         std::iter::from_fn(move || {
@@ -89,64 +96,277 @@ impl NearbyPathMap {
     }
 }
 
+/// The cheapest path from source to sink, inclusive of both ends, where stepping onto a cell costs cell_cost(cell).
+/// Ties between equally cheap paths go to the lowest total tiebreak(cell) along the path.
+/// Both must be non-negative, and costs positive. Returns None if the sink cannot be reached.
+pub fn calculate_weighted_path(source: usize, sink: usize, layout: &Layout, cell_cost: impl Fn(usize) -> i32, tiebreak: impl Fn(usize) -> i32) -> Option<Vec<usize>> {
+    let num_cells = layout.cells.len();
+    let mut costs = vec![(i32::MAX, i32::MAX); num_cells];
+    let mut previous = vec![None; num_cells];
+
+    let mut queue = BinaryHeap::new();
+    costs[source] = (0, 0);
+    queue.push(Reverse(((0, 0), source)));
+    while let Some(Reverse((cost, cell))) = queue.pop() {
+        if cost > costs[cell] { continue } // Already finalized with a cheaper path
+        if cell == sink { break }
+
+        for &neighbor in layout.cells[cell].neighbors.iter() {
+            let neighbor_cost = (cost.0 + cell_cost(neighbor), cost.1 + tiebreak(neighbor));
+            if neighbor_cost < costs[neighbor] {
+                costs[neighbor] = neighbor_cost;
+                previous[neighbor] = Some(cell);
+                queue.push(Reverse((neighbor_cost, neighbor)));
+            }
+        }
+    }
+    if costs[sink].0 == i32::MAX { return None }
+
+    let mut path = vec![sink];
+    while let Some(cell) = previous[*path.last().expect("empty path")] {
+        path.push(cell);
+    }
+    path.reverse();
+    Some(path)
+}
+
+/// Shortest path distances and first steps between every pair of cells, indexed by source * num_cells + sink
 pub struct PathMap {
-    sources: Box<[DistanceMap]>,
+    num_cells: usize,
+    distances: Box<[i32]>,
+    next_hops: Box<[Option<usize>]>,
 }
 impl PathMap {
     pub fn generate(layout: &Layout) -> Self {
-        let sources: Vec<DistanceMap> = (0..layout.cells.len()).map(|i| DistanceMap::generate(i, &layout)).collect();
+        let num_cells = layout.cells.len();
+
+        let mut distances = vec![i32::MAX; num_cells * num_cells];
+        let mut queue = Vec::with_capacity(num_cells);
+        for (source, distances) in distances.chunks_exact_mut(num_cells.max(1)).enumerate() {
+            distances[source] = 0;
+
+            queue.clear();
+            queue.push(source);
+            let mut head = 0;
+            while head < queue.len() {
+                let cell = queue[head];
+                head += 1;
+
+                let neighbor_distance = distances[cell] + 1;
+                for &neighbor in layout.cells[cell].neighbors.iter() {
+                    if neighbor_distance < distances[neighbor] {
+                        distances[neighbor] = neighbor_distance;
+                        queue.push(neighbor);
+                    }
+                }
+            }
+        }
+
+        // Ties go to the first neighbor, so paths are the same as when the neighbors were scanned on every step
+        let mut next_hops = Vec::with_capacity(num_cells * num_cells);
+        for source in 0..num_cells {
+            for sink in 0..num_cells {
+                let distances_to_sink = &distances[(sink * num_cells)..((sink + 1) * num_cells)]; // The distances are symmetrical, so can use the sink as a source
+                next_hops.push(layout.cells[source].neighbors.iter().cloned().min_by_key(|&n| distances_to_sink[n]));
+            }
+        }
+
         Self {
-            sources: sources.into_boxed_slice(),
+            num_cells,
+            distances: distances.into_boxed_slice(),
+            next_hops: next_hops.into_boxed_slice(),
         }
     }
 
     pub fn distance_between(&self, source: usize, sink: usize) -> i32 {
-        self.sources[source].distance_to(sink)
+        self.distances[source * self.num_cells + sink]
     }
 
-    pub fn step_towards(&self, source: usize, sink: usize, layout: &Layout) -> Option<usize> {
-        let distances_to_sink = &self.sources[sink].distances; // The distance map is symmetrical, so can use the sink as a source
-        let best = layout.cells[source].neighbors.iter().min_by_key(|n| distances_to_sink[**n]).cloned();
-        best
+    /// The distance from the source to every cell
+    pub fn distances_from(&self, source: usize) -> &[i32] {
+        &self.distances[(source * self.num_cells)..((source + 1) * self.num_cells)]
+    }
+
+    pub fn step_towards(&self, source: usize, sink: usize) -> Option<usize> {
+        self.next_hops[source * self.num_cells + sink]
+    }
+
+    /// Every shortest path from source to sink, rather than just the one that step_towards follows
+    #[allow(dead_code)]
+    pub fn shortest_path_dag(&self, source: usize, sink: usize, layout: &Layout) -> ShortestPathDag {
+        let length = self.distance_between(source, sink);
+        if length == i32::MAX {
+            return ShortestPathDag { layers: Vec::new(), successors: FnvHashMap::default() };
+        }
+
+        let mut layers = vec![vec![source]];
+        let mut successors = FnvHashMap::default();
+        for distance in 0..length {
+            let mut next_layer = Vec::new();
+            for &cell in layers[distance as usize].iter() {
+                // Any neighbor one step closer to the sink is also one step further from the source
+                let steps: Vec<usize> = layout.cells[cell].neighbors.iter().cloned().filter(|&n| self.distance_between(n, sink) == length - distance - 1).collect();
+                for &n in steps.iter() {
+                    if !next_layer.contains(&n) {
+                        next_layer.push(n);
+                    }
+                }
+                successors.insert(cell, steps);
+            }
+            layers.push(next_layer);
+        }
+
+        ShortestPathDag { layers, successors }
+    }
+
+    /// The cells along the shortest path from source to sink, inclusive of both ends
+    #[allow(dead_code)]
+    pub fn calculate_path(&self, source: usize, sink: usize) -> impl Iterator<Item=usize> + '_ {
+        let mut next = Some(source);
+        std::iter::from_fn(move || {
+            let output = next;
+            if let Some(current) = next {
+                if current == sink {
+                    next = None;
+                } else {
+                    next = self.step_towards(current, sink);
+                }
+            }
+            output
+        })
     }
 }
 
-pub struct DistanceMap {
-    distances: Box<[i32]>,
+/// All shortest paths between two cells. Cells are grouped into layers by their distance from the source,
+/// so the first layer is just the source, the last is just the sink, and every step goes from one layer to the next.
+#[allow(dead_code)]
+pub struct ShortestPathDag {
+    layers: Vec<Vec<usize>>,
+
+    /// cell -> neighbors in the next layer, in the order of the cell's neighbors
+    successors: FnvHashMap<usize, Vec<usize>>,
 }
-impl DistanceMap {
-    pub fn generate(source: usize, layout: &Layout) -> Self {
-        let mut lookup = FnvHashMap::default();
-        lookup.insert(source, 0);
+#[allow(dead_code)]
+impl ShortestPathDag {
+    /// Empty if the sink cannot be reached
+    pub fn layers(&self) -> &[Vec<usize>] {
+        &self.layers
+    }
 
-        let mut queue = VecDeque::new();
-        queue.push_back(source);
-
-        while let Some(cell) = queue.pop_front() {
-            let neighbor_distance = lookup[&cell] + 1;
-            for &neighbor in layout.cells[cell].neighbors.iter() {
-                if let Some(distance) = lookup.get_mut(&neighbor) {
-                    if neighbor_distance < *distance {
-                        *distance = neighbor_distance;
-                        queue.push_back(neighbor);
+    pub fn cells(&self) -> impl Iterator<Item=usize> + '_ {
+        self.layers.iter().flat_map(|layer| layer.iter().cloned())
+    }
+
+    pub fn successors(&self, cell: usize) -> &[usize] {
+        self.successors.get(&cell).map(|steps| steps.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn num_paths(&self) -> u64 {
+        let mut num_paths: FnvHashMap<usize, u64> = FnvHashMap::default();
+        for layer in self.layers.iter().rev() {
+            for &cell in layer.iter() {
+                let steps = self.successors(cell);
+                let count = if steps.is_empty() { 1 } else { steps.iter().map(|n| num_paths[n]).fold(0u64, |a, b| a.saturating_add(b)) };
+                num_paths.insert(cell, count);
+            }
+        }
+        self.layers.first().map(|layer| num_paths[&layer[0]]).unwrap_or(0)
+    }
+
+    /// The path from source to sink, inclusive of both ends, with the highest total score across its cells.
+    /// Ties go to the first neighbor, so with a constant score this is the same path as PathMap::calculate_path.
+    pub fn best_path(&self, score: impl Fn(usize) -> i32) -> Vec<usize> {
+        // Work backwards from the sink, remembering the best total score from each cell onwards
+        let mut best: FnvHashMap<usize, (i32,Option<usize>)> = FnvHashMap::default();
+        for layer in self.layers.iter().rev() {
+            for &cell in layer.iter() {
+                let mut best_step = None;
+                let mut best_remaining = 0;
+                for &n in self.successors(cell).iter() {
+                    let remaining = best[&n].0;
+                    if best_step.is_none() || remaining > best_remaining {
+                        best_step = Some(n);
+                        best_remaining = remaining;
                     }
-                } else {
-                    lookup.insert(neighbor, neighbor_distance);
-                    queue.push_back(neighbor);
                 }
+                best.insert(cell, (score(cell) + best_remaining, best_step));
             }
         }
 
-        let mut distances = Vec::new();
-        distances.resize(layout.cells.len(), i32::MAX);
-        for (&index, &distance) in lookup.iter() {
-            distances[index] = distance;
+        let mut path = Vec::new();
+        let mut next = self.layers.first().map(|layer| layer[0]);
+        while let Some(cell) = next {
+            path.push(cell);
+            next = best[&cell].1;
         }
+        path
+    }
+}
 
-        Self {
-            distances: distances.into_boxed_slice(),
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use super::*;
+    use crate::hex::Hex;
+    use crate::mapgen;
+
+    /// Counts every walk from cell to sink that takes exactly steps_left steps, which are the shortest paths when steps_left is the distance
+    fn count_paths_by_brute_force(cell: usize, sink: usize, steps_left: i32, layout: &Layout) -> u64 {
+        if steps_left == 0 {
+            return if cell == sink { 1 } else { 0 };
+        }
+        layout.cells[cell].neighbors.iter().map(|&n| count_paths_by_brute_force(n, sink, steps_left - 1, layout)).sum()
+    }
+
+    #[test]
+    fn shortest_path_dag_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for seed in 0..10 {
+            let (layout, _) = mapgen::generate(&mut StdRng::seed_from_u64(seed));
+            let paths = PathMap::generate(&layout);
+            for _ in 0..20 {
+                let source = rng.gen_range(0..layout.cells.len());
+                let sink = rng.gen_range(0..layout.cells.len());
+                let distance = paths.distance_between(source, sink);
+                if distance > 8 { continue } // Brute force grows exponentially with the distance
+
+                let dag = paths.shortest_path_dag(source, sink, &layout);
+                assert_eq!(dag.layers().len() as i32, distance + 1);
+                assert_eq!(dag.num_paths(), count_paths_by_brute_force(source, sink, distance, &layout), "map {}: {} -> {}", seed, source, sink);
+                assert_eq!(dag.best_path(|_| 0), paths.calculate_path(source, sink).collect::<Vec<_>>(), "map {}: {} -> {}", seed, source, sink);
+            }
         }
     }
 
-    pub fn distance_to(&self, index: usize) -> i32 { self.distances[index] }
-}
\ No newline at end of file
+    #[test]
+    fn weighted_path_avoids_costly_cells_and_breaks_ties() {
+        // A diamond where cells 1 and 2 are both one step from cells 0 and 3
+        let hexes = [Hex::new(0, 0), Hex::new(1, 0), Hex::new(1, -1), Hex::new(2, -1)];
+        let cells: Vec<CellLayout> = hexes.iter().map(|hex| {
+            let mut directions = [None; NUM_DIRECTIONS];
+            for (direction, neighbor) in directions.iter_mut().enumerate() {
+                *neighbor = hexes.iter().position(|&other| other == hex.neighbor(direction));
+            }
+            CellLayout::new(None, 0, directions)
+        }).collect();
+        let layout = Layout { cells: cells.into_boxed_slice(), bases: [Box::new([0]), Box::new([3])] };
+
+        assert_eq!(calculate_weighted_path(0, 3, &layout, |cell| if cell == 1 { 5 } else { 1 }, |_| 0), Some(vec![0, 2, 3]));
+        assert_eq!(calculate_weighted_path(0, 3, &layout, |_| 1, |cell| if cell == 1 { 1 } else { 0 }), Some(vec![0, 2, 3]));
+        assert_eq!(calculate_weighted_path(0, 3, &layout, |_| 1, |cell| if cell == 2 { 1 } else { 0 }), Some(vec![0, 1, 3]));
+    }
+
+    #[test]
+    fn shortest_path_dag_is_empty_when_unreachable() {
+        let layout = Layout {
+            cells: vec![CellLayout::new(None, 0, [None; NUM_DIRECTIONS]), CellLayout::new(None, 0, [None; NUM_DIRECTIONS])].into_boxed_slice(),
+            bases: [Box::new([0]), Box::new([1])],
+        };
+        let dag = PathMap::generate(&layout).shortest_path_dag(0, 1, &layout);
+        assert!(dag.layers().is_empty());
+        assert_eq!(dag.cells().count(), 0);
+        assert_eq!(dag.num_paths(), 0);
+        assert!(dag.best_path(|_| 0).is_empty());
+    }
+}
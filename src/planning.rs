@@ -8,6 +8,9 @@ use super::valuation::HarvestEvaluator;
 use super::pathing::NearbyPathMap;
 use super::view::*;
 
+/// How many times to search for a detour, each time with the ants spread across the previous detour
+const MAX_DETOUR_SEARCHES: usize = 3;
+
 #[derive(Clone,PartialEq,Eq,Hash)]
 pub enum Milestone {
     Harvest(usize),
@@ -70,8 +73,29 @@ pub fn enact_plan(player: usize, plan: &[Milestone], view: &View, state: &State)
                 let new_spread = initial_spread + distance;
                 let new_collection_rate = evaluator.calculate_harvest_rate(num_harvests + 1, new_spread);
                 if new_collection_rate > initial_collection_rate {
-                    let ants_per_cell = state.total_ants[player] / new_spread;
-                    for cell in nearby.calculate_path(source, target, &view.layout, &view.paths) {
+                    let mut ants_per_cell = state.total_ants[player] / new_spread;
+                    let mut path: Vec<usize> = nearby.calculate_path(source, target, &view.layout, &view.paths).collect();
+                    if path.iter().any(|&cell| attacks[cell] > ants_per_cell) {
+                        // Detour around cells the enemy can attack when that takes fewer ants than holding them.
+                        // A detour is longer so spreads the ants thinner, which changes the threat costs, so search again until they settle.
+                        let mut detour_ants_per_cell = ants_per_cell;
+                        for _ in 0..MAX_DETOUR_SEARCHES {
+                            let detour = match nearby.calculate_weighted_path(source, target, &view.layout, |cell| calculate_threat_cost(attacks[cell], detour_ants_per_cell)) {
+                                Some(detour) => detour,
+                                None => break,
+                            };
+                            let num_new_cells = detour.iter().skip(1).filter(|cell| !beacons.contains(*cell)).count() as i32;
+                            let detour_spread = initial_spread + num_new_cells;
+                            if evaluator.calculate_harvest_rate(num_harvests + 1, detour_spread) <= initial_collection_rate { break } // Not worth it, so fight on the direct path
+
+                            let previous_ants_per_cell = detour_ants_per_cell;
+                            detour_ants_per_cell = state.total_ants[player] / detour_spread.max(1);
+                            path = detour;
+                            ants_per_cell = detour_ants_per_cell;
+                            if detour_ants_per_cell == previous_ants_per_cell { break }
+                        }
+                    }
+                    for cell in path {
                         if attacks[cell] > ants_per_cell { break } // Stop if we cannot gain anything from harvesting this cell
 
                         beacons.insert(cell);
@@ -87,12 +111,23 @@ pub fn enact_plan(player: usize, plan: &[Milestone], view: &View, state: &State)
         }
     }
 
+    // Thicken the chain on cells that the enemy can attack. The weights are only relative because spread_ants_across_beacons
+    // rescales them to the ants we have, so this shifts ants towards contested cells but does not guarantee enough to hold them.
+    let ants_per_beacon = state.total_ants[player] / (beacons.len() as i32).max(1);
+    let weighted_beacons = beacons.into_iter().map(|cell| (cell, ants_per_beacon.max(attacks[cell] + 1)));
+
     Commands {
-        assignments: movement::spread_ants_across_beacons(beacons.into_iter(), player, view, state),
+        assignments: movement::spread_ants_across_beacons(weighted_beacons, player, view, state),
         harvests,
     }
 }
 
+/// The number of cells' worth of ants needed to hold a cell against the enemy's strongest attack chain, which a chain of equal strength can do
+fn calculate_threat_cost(attack: i32, ants_per_cell: i32) -> i32 {
+    let ants_per_cell = ants_per_cell.max(1);
+    ((attack + ants_per_cell - 1) / ants_per_cell).max(1) // Holding takes as many ants as the attack, like the cutoff in enact_plan
+}
+
 pub struct Commands {
     pub assignments: Box<[i32]>,
     pub harvests: Vec<usize>,
@@ -115,3 +150,86 @@ impl Display for Commands {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex::Hex;
+    use crate::inputs::*;
+
+    const MY_BASE: usize = 0;
+    const CONTESTED: usize = 2;
+    const TARGET: usize = 4;
+    const ENEMY_BASE: usize = 5;
+    const DETOUR: [usize; 6] = [6, 7, 8, 9, 10, 11];
+
+    /// A row of cells from my base to a crystal, with an enemy base below the middle of the row
+    /// and a longer loop above the row that avoids the middle
+    fn detour_map() -> View {
+        let hexes = [
+            Hex::new(0, 0), Hex::new(1, 0), Hex::new(2, 0), Hex::new(3, 0), Hex::new(4, 0),
+            Hex::new(2, 1),
+            Hex::new(0, -1), Hex::new(1, -2), Hex::new(2, -2), Hex::new(3, -2), Hex::new(4, -2), Hex::new(4, -1),
+        ];
+        let cells: Vec<CellLayout> = hexes.iter().enumerate().map(|(cell, hex)| {
+            let mut directions = [None; NUM_DIRECTIONS];
+            for (direction, neighbor) in directions.iter_mut().enumerate() {
+                *neighbor = hexes.iter().position(|&other| other == hex.neighbor(direction));
+            }
+            if cell == TARGET {
+                CellLayout::new(Some(Content::Crystals), 100, directions)
+            } else {
+                CellLayout::new(None, 0, directions)
+            }
+        }).collect();
+
+        View::new(Layout {
+            cells: cells.into_boxed_slice(),
+            bases: [Box::new([MY_BASE]), Box::new([ENEMY_BASE])],
+        })
+    }
+
+    /// 20 of my ants at my base, and a chain of enemy ants of the given strength from their base into the middle of the row
+    fn enact_with_enemy_chain(enemy_chain: i32, view: &View) -> Commands {
+        let num_cells = view.layout.cells.len();
+        let mut num_ants = [vec![0; num_cells], vec![0; num_cells]];
+        num_ants[ME][MY_BASE] = 20;
+        num_ants[ENEMY][ENEMY_BASE] = enemy_chain;
+        num_ants[ENEMY][CONTESTED] = enemy_chain;
+        let resources: Vec<i32> = view.layout.cells.iter().map(|cell| cell.initial_resources).collect();
+
+        let state = State::new(0, num_ants.map(|ants| ants.into_boxed_slice()), resources.into_boxed_slice(), [0, 0]);
+        enact_plan(ME, &[Milestone::Harvest(TARGET)], view, &state)
+    }
+
+    #[test]
+    fn detours_around_a_much_stronger_chain() {
+        let view = detour_map();
+        let commands = enact_with_enemy_chain(40, &view);
+
+        assert_eq!(commands.harvests, vec![TARGET]);
+        assert_eq!(commands.assignments[CONTESTED], 0);
+        assert!(DETOUR.iter().all(|&cell| commands.assignments[cell] > 0), "assignments {:?}", commands.assignments);
+        assert!(commands.assignments[TARGET] > 0);
+    }
+
+    #[test]
+    fn fights_through_when_the_detour_costs_more() {
+        // Holding the contested cell takes two cells' worth of ants, which is less than the three extra cells of the detour
+        let view = detour_map();
+        let commands = enact_with_enemy_chain(10, &view);
+
+        assert_eq!(commands.harvests, vec![TARGET]);
+        assert!(DETOUR.iter().all(|&cell| commands.assignments[cell] == 0), "assignments {:?}", commands.assignments);
+        assert!(commands.assignments[1] > 0);
+    }
+
+    #[test]
+    fn threat_cost_counts_an_equal_chain_as_holdable() {
+        assert_eq!(calculate_threat_cost(0, 5), 1);
+        assert_eq!(calculate_threat_cost(5, 5), 1);
+        assert_eq!(calculate_threat_cost(6, 5), 2);
+        assert_eq!(calculate_threat_cost(10, 5), 2);
+        assert_eq!(calculate_threat_cost(3, 0), 3);
+    }
+}
@@ -0,0 +1,185 @@
+//! Replay files capture everything the bot saw and said during a game, one line per entry,
+//! each prefixed with the channel it came from so the game can be reconstructed offline.
+
+use std::cell::{Cell,RefCell};
+use std::fs::File;
+use std::io::{self,BufRead,BufReader,BufWriter,Read,Write};
+use std::path::Path;
+
+pub const INPUT_PREFIX: &str = "< ";
+pub const OUTPUT_PREFIX: &str = "> ";
+pub const DIAGNOSTIC_PREFIX: &str = "! ";
+pub const CONFIG_PREFIX: &str = "# ";
+
+thread_local! {
+    static RECORDER: RefCell<Option<Recorder>> = const { RefCell::new(None) };
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Write to stderr, and also to the replay file if one is being recorded
+#[macro_export]
+macro_rules! diagnostic {
+    ($($arg:tt)*) => {{
+        $crate::recording::emit_diagnostic(&format!($($arg)*));
+    }}
+}
+
+struct Recorder {
+    writer: BufWriter<File>,
+
+    /// Input arrives in arbitrary chunks, so hold onto any incomplete line until its newline arrives
+    pending_input: Vec<u8>,
+}
+impl Recorder {
+    fn write_lines(&mut self, prefix: &str, text: &str) {
+        for line in text.lines() {
+            // Recording is best-effort, it must never take the bot down
+            let _ = writeln!(self.writer, "{}{}", prefix, line);
+        }
+    }
+
+    fn write_input(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if byte == b'\n' {
+                let line = String::from_utf8_lossy(&self.pending_input);
+                let _ = writeln!(self.writer, "{}{}", INPUT_PREFIX, line.trim_end_matches('\r'));
+                self.pending_input.clear();
+            } else {
+                self.pending_input.push(byte);
+            }
+        }
+    }
+}
+
+/// Start recording everything on this thread to a new replay file at the given path
+pub fn start(path: impl AsRef<Path>) -> io::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    RECORDER.with(|recorder| {
+        *recorder.borrow_mut() = Some(Recorder {
+            writer,
+            pending_input: Vec::new(),
+        });
+    });
+    Ok(())
+}
+
+pub fn record_output(text: &str) {
+    with_recorder(|recorder| recorder.write_lines(OUTPUT_PREFIX, text));
+}
+
+/// Record a setting that the game depends on, such as the agent's seed, as a key=value line
+pub fn record_config(key: &str, value: &str) {
+    with_recorder(|recorder| recorder.write_lines(CONFIG_PREFIX, &format!("{}={}", key, value)));
+}
+
+pub fn emit_diagnostic(text: &str) {
+    if !QUIET.with(|quiet| quiet.get()) {
+        eprintln!("{}", text);
+    }
+    with_recorder(|recorder| recorder.write_lines(DIAGNOSTIC_PREFIX, text));
+}
+
+/// Stop diagnostics on this thread going to stderr, e.g. when running many games at once
+#[allow(dead_code)]
+pub fn set_quiet(quiet: bool) {
+    QUIET.with(|q| q.set(quiet));
+}
+
+/// Push everything recorded so far to disk. Called every turn because the bot is normally killed rather than exiting cleanly.
+pub fn flush() {
+    with_recorder(|recorder| {
+        let _ = recorder.writer.flush();
+    });
+}
+
+fn with_recorder(f: impl FnOnce(&mut Recorder)) {
+    RECORDER.with(|recorder| {
+        if let Some(recorder) = recorder.borrow_mut().as_mut() {
+            f(recorder);
+        }
+    });
+}
+
+/// Wraps an input source and records every line read through it
+pub struct RecordingReader<R> {
+    inner: R,
+}
+impl<R: BufRead> RecordingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner }
+    }
+}
+impl<R: BufRead> Read for RecordingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let num_read = self.inner.read(buf)?;
+        with_recorder(|recorder| recorder.write_input(&buf[..num_read]));
+        Ok(num_read)
+    }
+}
+impl<R: BufRead> BufRead for RecordingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if amt == 0 { return }
+        if let Ok(buf) = self.inner.fill_buf() {
+            let consumed = &buf[..amt.min(buf.len())];
+            with_recorder(|recorder| recorder.write_input(consumed));
+        }
+        self.inner.consume(amt);
+    }
+}
+
+/// The contents of a replay file, split back out into its channels
+#[allow(dead_code)]
+pub struct Replay {
+    /// Protocol input exactly as the bot received it, ready to be fed into a ProtocolReader
+    pub input: String,
+
+    /// tick -> action line emitted
+    pub outputs: Vec<String>,
+
+    pub diagnostics: Vec<String>,
+
+    /// key=value settings, in the order they were recorded
+    pub config: Vec<(String,String)>,
+}
+#[allow(dead_code)]
+impl Replay {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse(BufReader::new(File::open(path)?))
+    }
+
+    pub fn parse(reader: impl BufRead) -> io::Result<Self> {
+        let mut replay = Self {
+            input: String::new(),
+            outputs: Vec::new(),
+            diagnostics: Vec::new(),
+            config: Vec::new(),
+        };
+
+        for line in reader.lines() {
+            let line = line?;
+            if let Some(input) = line.strip_prefix(INPUT_PREFIX) {
+                replay.input.push_str(input);
+                replay.input.push('\n');
+            } else if let Some(output) = line.strip_prefix(OUTPUT_PREFIX) {
+                replay.outputs.push(output.to_string());
+            } else if let Some(diagnostic) = line.strip_prefix(DIAGNOSTIC_PREFIX) {
+                replay.diagnostics.push(diagnostic.to_string());
+            } else if let Some(setting) = line.strip_prefix(CONFIG_PREFIX) {
+                if let Some((key, value)) = setting.split_once('=') {
+                    replay.config.push((key.to_string(), value.to_string()));
+                }
+            }
+        }
+
+        Ok(replay)
+    }
+
+    /// The last value recorded for the setting, if any
+    pub fn config_value(&self, key: &str) -> Option<&str> {
+        self.config.iter().rev().find(|(k, _)| k == key).map(|(_, value)| value.as_str())
+    }
+}
@@ -1,25 +1,80 @@
+use std::fmt::Display;
+
 use super::harvesting::HarvestMap;
 use super::inputs::*;
-use super::movement::{self,AssignmentsPerPlayer};
+use super::movement::{self,AssignmentsPerPlayer,MovementModel};
 use super::view::*;
 
-pub fn forward(assignments: &AssignmentsPerPlayer, view: &View, state: &mut State) {
+/// Something that happened during a tick of the simulation
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Event {
+    /// A new tick has started
+    Ticked { tick: u32 },
+
+    /// Ants stepped from source into the neighboring target cell
+    Moved { player: usize, source: usize, target: usize, num_ants: i32 },
+
+    /// Both players had ants on the cell, with these attack chain strengths
+    Contested { cell: usize, attack_chains: [i32; NUM_PLAYERS] },
+
+    /// The player gained this amount, which can add up to more than the cell had left when both players harvest it
+    Harvested { player: usize, cell: usize, content: Content, amount: i32 },
+
+    /// The cell's resources went down by this amount
+    Depleted { cell: usize, amount: i32 },
+
+    Spawned { player: usize, base: usize, num_ants: i32 },
+}
+impl Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ticked { tick } => write!(f, "tick {}", tick),
+            Self::Moved { player, source, target, num_ants } => write!(f, "player {} moved {} ants {} -> {}", player, num_ants, source, target),
+            Self::Contested { cell, attack_chains } => write!(f, "cell {} contested: attack chains {} vs {}", cell, attack_chains[ME], attack_chains[ENEMY]),
+            Self::Harvested { player, cell, content, amount } => write!(f, "player {} harvested {} {:?} from cell {}", player, amount, content, cell),
+            Self::Depleted { cell, amount } => write!(f, "cell {} depleted by {}", cell, amount),
+            Self::Spawned { player, base, num_ants } => write!(f, "player {} spawned {} ants at base {}", player, num_ants, base),
+        }
+    }
+}
+
+/// Receives events as the simulation runs. Use () to ignore them, or a Vec to collect them.
+pub trait EventLog {
+    fn log(&mut self, event: Event);
+}
+impl EventLog for () {
+    #[inline]
+    fn log(&mut self, _event: Event) {}
+}
+impl EventLog for Vec<Event> {
+    fn log(&mut self, event: Event) {
+        self.push(event);
+    }
+}
+
+pub fn forward(assignments: &AssignmentsPerPlayer, model: MovementModel, view: &View, state: &mut State) {
+    forward_with_log(assignments, model, view, state, &mut ());
+}
+
+/// Same as forward, but explains what happened during the tick
+pub fn forward_with_log(assignments: &AssignmentsPerPlayer, model: MovementModel, view: &View, state: &mut State, log: &mut impl EventLog) {
     state.tick += 1;
-    apply_movement(assignments, view, state);
-    apply_harvest(view, state);
+    log.log(Event::Ticked { tick: state.tick });
+    apply_movement(assignments, model, view, state, log);
+    apply_harvest(view, state, log);
 }
 
-fn apply_movement(assignments: &AssignmentsPerPlayer, view: &View, state: &mut State) {
+fn apply_movement(assignments: &AssignmentsPerPlayer, model: MovementModel, view: &View, state: &mut State, log: &mut impl EventLog) {
     for player in 0..NUM_PLAYERS {
         let assignments = &assignments[player];
         let num_ants = &mut state.num_ants[player];
 
-        movement::move_ants_for_player(assignments, view, num_ants);
+        movement::move_ants_for_player(player, assignments, model, view, num_ants, log);
     }
 }
 
-fn apply_harvest(view: &View, state: &mut State) {
-    let harvest_map = HarvestMap::generate(view, &state.num_ants);
+fn apply_harvest(view: &View, state: &mut State, log: &mut impl EventLog) {
+    let harvest_map = HarvestMap::generate(view, &state.num_ants, log);
     for cell in 0..view.layout.cells.len() {
         let available = &mut state.resources[cell];
         if *available <= 0 { continue }
@@ -35,6 +90,7 @@ fn apply_harvest(view: &View, state: &mut State) {
             if harvest <= 0 { continue }
 
             reduction += harvest;
+            log.log(Event::Harvested { player, cell, content, amount: harvest });
 
             match content {
                 Content::Crystals => {
@@ -49,18 +105,17 @@ fn apply_harvest(view: &View, state: &mut State) {
                         state.num_ants[player][base] += spawn_at_this_base;
                         state.total_ants[player] += spawn_at_this_base;
                         remaining -= spawn_at_this_base;
+                        if spawn_at_this_base > 0 {
+                            log.log(Event::Spawned { player, base, num_ants: spawn_at_this_base });
+                        }
                     }
                 },
             }
         }
         if reduction <= 0 { continue }
 
-        let next = *available - reduction;
-        if next < 0 {
-            *available = 0;
-
-        } else {
-            *available = next;
-        }
+        let next = (*available - reduction).max(0);
+        log.log(Event::Depleted { cell, amount: *available - next });
+        *available = next;
     }
 }
\ No newline at end of file
@@ -13,6 +13,24 @@ const QUANTILE_SAMPLE_LIMIT: usize = 32;
 
 const LEARNING_RATE: f32 = 0.01;
 
+/// Tuning for the search, so that the tournament can compare settings
+#[derive(Clone,Debug)]
+pub struct SolverConfig {
+    /// Quantiles are raised to this power when choosing between options, so higher values favour the best options more strongly
+    pub selection_power: i32,
+
+    /// How far each result moves the quantiles learned so far
+    pub learning_rate: f32,
+}
+impl Default for SolverConfig {
+    fn default() -> Self {
+        Self {
+            selection_power: SELECTION_POWER,
+            learning_rate: LEARNING_RATE,
+        }
+    }
+}
+
 #[derive(Copy,Clone,Debug)]
 enum SolverType {
     Generation,
@@ -47,14 +65,16 @@ impl SolverStats {
 
 pub struct Solver {
     player: usize,
+    config: SolverConfig,
     solver_quantiles: [f32; NUM_SOLVERS],
     generator: PheromoneMatrix,
     mutator: Mutator,
 }
 impl Solver {
-    pub fn new(player: usize, view: &View) -> Self {
+    pub fn new(player: usize, view: &View, config: SolverConfig) -> Self {
         Self {
             player,
+            config,
             solver_quantiles: [INITIAL_QUANTILE; NUM_SOLVERS],
             generator: PheromoneMatrix::new(player, view),
             mutator: Mutator::new(),
@@ -63,17 +83,17 @@ impl Solver {
 
     pub fn step(&mut self, session: &mut SolverSession, countermoves: &Vec<Milestone>, view: &View, state: &State, rng: &mut StdRng) {
         // Generate solution
-        let solver = SOLVERS[select_weighted(&self.solver_quantiles, rng)];
+        let solver = SOLVERS[select_weighted(&self.solver_quantiles, &self.config, rng)];
         let (plan, lesson) = match solver {
             SolverType::Generation => {
-                let (plan, walks) = self.generator.generate(rng, |cell| {
+                let (plan, walks) = self.generator.generate(&self.config, rng, |cell| {
                     state.resources[cell] > 0
                 });
                 (plan, Lesson::Generation(walks))
             },
             SolverType::Mutation => {
                 let mut plan = session.best.plan.clone();
-                let mutation = self.mutator.mutate(&mut plan, rng);
+                let mutation = self.mutator.mutate(&mut plan, &self.config, rng);
                 (plan, Lesson::Mutation(mutation))
             },
         };
@@ -86,10 +106,10 @@ impl Solver {
         // Learn quantiles
         let quantile = session.scorer.quantile(candidate.score);
         session.scorer.insert(candidate.score);
-        learn_quantile(&mut self.solver_quantiles[solver as usize], quantile);
+        learn_quantile(&mut self.solver_quantiles[solver as usize], quantile, &self.config);
         match lesson {
-            Lesson::Generation(walks) => self.generator.learn(quantile, &walks),
-            Lesson::Mutation(mutation) => self.mutator.learn(quantile, mutation),
+            Lesson::Generation(walks) => self.generator.learn(quantile, &walks, &self.config),
+            Lesson::Mutation(mutation) => self.mutator.learn(quantile, mutation, &self.config),
         }
 
         // Update best
@@ -279,6 +299,9 @@ impl QuantileEstimator {
         offset
     }
 }
+impl Default for QuantileEstimator {
+    fn default() -> Self { Self::new() }
+}
 
 
 pub struct PheromoneMatrix {
@@ -348,7 +371,7 @@ impl PheromoneMatrix {
         }
     }
 
-    pub fn generate(&self, rng: &mut StdRng, is_allowed: impl Fn(usize) -> bool) -> (Vec<Milestone>,Box<[Walk]>) {
+    pub fn generate(&self, config: &SolverConfig, rng: &mut StdRng, is_allowed: impl Fn(usize) -> bool) -> (Vec<Milestone>,Box<[Walk]>) {
         let mut allowed: Vec<bool> = self.veins.iter().map(|&cell| is_allowed(cell)).collect();
         let mut num_remaining = allowed.iter().filter(|&&allowed| allowed).count() as i32;
 
@@ -372,7 +395,7 @@ impl PheromoneMatrix {
             let mut total = 0.0;
             for vein in 0..quantiles.len() {
                 if allowed[vein] {
-                    total += quantiles[vein].powi(SELECTION_POWER);
+                    total += quantiles[vein].powi(config.selection_power);
                 }
             }
 
@@ -382,7 +405,7 @@ impl PheromoneMatrix {
             let mut selected = None;
             for vein in 0..quantiles.len() {
                 if allowed[vein] {
-                    cumulative += quantiles[vein].powi(SELECTION_POWER);
+                    cumulative += quantiles[vein].powi(config.selection_power);
                     if selector <= cumulative {
                         selected = Some(vein);
                         break;
@@ -405,7 +428,7 @@ impl PheromoneMatrix {
         (priorities, walks.into_boxed_slice())
     }
 
-    pub fn learn(&mut self, quantile: Quantile, walks: &[Walk]) {
+    pub fn learn(&mut self, quantile: Quantile, walks: &[Walk], config: &SolverConfig) {
         for walk in walks.iter() {
             let mut previous = None;
             for &cell in walk.veins.iter() {
@@ -416,7 +439,7 @@ impl PheromoneMatrix {
                         } else {
                             &mut self.head_quantiles[walk.base_id]
                         };
-                    learn_quantile(&mut quantiles[vein], quantile);
+                    learn_quantile(&mut quantiles[vein], quantile, config);
 
                     previous = Some(vein);
                 }
@@ -487,8 +510,8 @@ impl Mutator {
         }
     }
 
-    pub fn mutate(&self, plan: &mut Vec<Milestone>, rng: &mut StdRng) -> Mutation {
-        let mutation = MUTATIONS[select_weighted(&self.mutation_quantiles, rng)];
+    pub fn mutate(&self, plan: &mut Vec<Milestone>, config: &SolverConfig, rng: &mut StdRng) -> Mutation {
+        let mutation = MUTATIONS[select_weighted(&self.mutation_quantiles, config, rng)];
         match mutation {
             Mutation::Bubble => bubble_mutation(plan, rng),
             Mutation::Move => move_mutation(plan, rng),
@@ -500,10 +523,13 @@ impl Mutator {
         mutation
     }
 
-    pub fn learn(&mut self, quantile: Quantile, mutation: Mutation) {
-        learn_quantile(&mut self.mutation_quantiles[mutation as usize], quantile);
+    pub fn learn(&mut self, quantile: Quantile, mutation: Mutation, config: &SolverConfig) {
+        learn_quantile(&mut self.mutation_quantiles[mutation as usize], quantile, config);
     }
 }
+impl Default for Mutator {
+    fn default() -> Self { Self::new() }
+}
 impl Display for Mutator {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut is_first = true;
@@ -588,17 +614,17 @@ fn barrier_mutation(plan: &mut Vec<Milestone>, rng: &mut StdRng) {
 }
 
 
-fn learn_quantile(weight: &mut f32, quantile: Quantile) {
-    *weight = (1.0 - LEARNING_RATE) * *weight + LEARNING_RATE * quantile.f32();
+fn learn_quantile(weight: &mut f32, quantile: Quantile, config: &SolverConfig) {
+    *weight = (1.0 - config.learning_rate) * *weight + config.learning_rate * quantile.f32();
 }
 
-fn select_weighted(weights: &[f32], rng: &mut StdRng) -> usize {
-    let total = weights.iter().map(|x| x.powi(SELECTION_POWER)).sum::<f32>();
+fn select_weighted(weights: &[f32], config: &SolverConfig, rng: &mut StdRng) -> usize {
+    let total = weights.iter().map(|x| x.powi(config.selection_power)).sum::<f32>();
     let selector = total * rng.gen::<f32>();
 
     let mut cumulative = 0.0;
     for (index, &quantile) in weights.iter().enumerate() {
-        cumulative += quantile.powi(SELECTION_POWER);
+        cumulative += quantile.powi(config.selection_power);
         if selector <= cumulative {
             return index;
         }
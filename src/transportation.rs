@@ -0,0 +1,387 @@
+use super::pathing::PathMap;
+
+/// The cost of an edge between cells that cannot reach each other, which can never carry flow
+const UNREACHABLE: i32 = i32::MAX;
+
+/// Ants to send from a source cell to a sink cell
+pub struct Shipment {
+    pub source: usize,
+    pub sink: usize,
+    pub amount: i32,
+}
+
+/// Solves the transportation problem exactly: moves as many ants as possible from cells with excess to cells with a shortfall,
+/// with the least total distance travelled. Cells with positive excess are sources and cells with negative excess are sinks.
+/// Nothing is sent between cells that cannot reach each other.
+pub fn solve(excess: &[i32], paths: &PathMap) -> Vec<Shipment> {
+    let sources: Vec<Node> = (0..excess.len()).filter(|&cell| excess[cell] > 0).map(|cell| Node::new(cell, excess[cell])).collect();
+    let sinks: Vec<Node> = (0..excess.len()).filter(|&cell| excess[cell] < 0).map(|cell| Node::new(cell, -excess[cell])).collect();
+    if sources.is_empty() || sinks.is_empty() { return Vec::new() }
+
+    let mut network = Network {
+        costs: sources.iter().flat_map(|source| sinks.iter().map(move |sink| paths.distance_between(source.cell, sink.cell))).collect(),
+        flows: vec![0; sources.len() * sinks.len()],
+        sources,
+        sinks,
+        queue: BucketQueue::default(),
+    };
+    network.solve();
+
+    let num_sinks = network.sinks.len();
+    let mut shipments = Vec::new();
+    for (i, source) in network.sources.iter().enumerate() {
+        for (j, sink) in network.sinks.iter().enumerate() {
+            let amount = network.flows[i * num_sinks + j];
+            if amount > 0 {
+                shipments.push(Shipment { source: source.cell, sink: sink.cell, amount });
+            }
+        }
+    }
+    shipments
+}
+
+struct Node {
+    cell: usize,
+
+    /// Supply left to send from a source, or demand left to fill at a sink
+    remaining: i32,
+
+    /// Keeps the reduced costs non-negative so that Dijkstra works even though sending flow back along an edge has a negative cost
+    potential: i32,
+
+    /// Working space for Dijkstra
+    distance: i32,
+    done: bool,
+
+    /// Cannot reach a sink with demand remaining along shortest paths in the current phase
+    dead: bool,
+    on_path: bool,
+
+    /// Edges before this one have already failed to reach a sink in the current phase
+    next_edge: usize,
+}
+impl Node {
+    fn new(cell: usize, remaining: i32) -> Self {
+        Self { cell, remaining, potential: 0, distance: 0, done: false, dead: false, on_path: false, next_edge: 0 }
+    }
+}
+
+/// A primal-dual min cost flow on the complete bipartite graph from sources to sinks.
+/// Each phase finds the shortest path distances with Dijkstra, then pushes as much flow as possible along paths that are all shortest.
+/// Costs are distances on a small map, so there are only a few phases.
+/// Edges are indexed by source * num_sinks + sink.
+struct Network {
+    sources: Vec<Node>,
+    sinks: Vec<Node>,
+    costs: Vec<i32>,
+    flows: Vec<i32>,
+    queue: BucketQueue,
+}
+impl Network {
+    fn solve(&mut self) {
+        while self.sources.iter().any(|source| source.remaining > 0) && self.sinks.iter().any(|sink| sink.remaining > 0) {
+            if !self.update_potentials() { break }
+
+            for node in self.sources.iter_mut().chain(self.sinks.iter_mut()) {
+                node.dead = false;
+                node.next_edge = 0;
+            }
+            for source in 0..self.sources.len() {
+                while self.sources[source].remaining > 0 {
+                    let pushed = self.push_from_source(source, self.sources[source].remaining);
+                    if pushed <= 0 { break }
+                    self.sources[source].remaining -= pushed;
+                }
+            }
+        }
+    }
+
+    /// Dijkstra from every source with supply remaining, then shifts the potentials so that the shortest paths have zero reduced cost.
+    /// Returns false if no sink with demand remaining can be reached.
+    fn update_potentials(&mut self) -> bool {
+        let num_sources = self.sources.len();
+        let num_sinks = self.sinks.len();
+        for node in self.sources.iter_mut().chain(self.sinks.iter_mut()) {
+            node.distance = i32::MAX;
+            node.done = false;
+        }
+        self.queue.clear();
+        for source in 0..num_sources {
+            if self.sources[source].remaining > 0 {
+                self.relax(source, 0);
+            }
+        }
+
+        // Stop at the first sink with demand remaining, as anything further away will have the same potential
+        let mut target_distance = i32::MAX;
+        while let Some((distance, node)) = self.queue.pop() {
+            if node < num_sources {
+                let source = &mut self.sources[node];
+                if source.done { continue }
+                source.done = true;
+
+                let base = distance + source.potential;
+                for sink in 0..num_sinks {
+                    let cost = self.costs[node * num_sinks + sink];
+                    if cost == UNREACHABLE { continue }
+                    let reduced = base + cost - self.sinks[sink].potential;
+                    self.relax(num_sources + sink, reduced);
+                }
+
+            } else {
+                let sink = &mut self.sinks[node - num_sources];
+                if sink.done { continue }
+                sink.done = true;
+                if sink.remaining > 0 {
+                    target_distance = distance;
+                    break;
+                }
+
+                let base = distance + sink.potential;
+                for source in 0..num_sources {
+                    let edge = source * num_sinks + node - num_sources;
+                    if self.flows[edge] > 0 {
+                        let reduced = base - self.costs[edge] - self.sources[source].potential;
+                        self.relax(source, reduced);
+                    }
+                }
+            }
+        }
+        if target_distance == i32::MAX { return false }
+
+        for node in self.sources.iter_mut().chain(self.sinks.iter_mut()) {
+            node.potential += node.distance.min(target_distance);
+        }
+        true
+    }
+
+    /// Nodes are numbered with sources first then sinks
+    fn relax(&mut self, node: usize, distance: i32) {
+        let num_sources = self.sources.len();
+        let node_distance = if node < num_sources { &mut self.sources[node].distance } else { &mut self.sinks[node - num_sources].distance };
+        if distance < *node_distance {
+            *node_distance = distance;
+            self.queue.push(distance, node);
+        }
+    }
+
+    /// Sends up to limit units from the source to any sink with demand remaining, using only zero reduced cost edges.
+    /// Edges that fail are skipped for the rest of the phase.
+    fn push_from_source(&mut self, source: usize, limit: i32) -> i32 {
+        let num_sinks = self.sinks.len();
+        self.sources[source].on_path = true;
+        let mut sink = self.sources[source].next_edge;
+        while sink < num_sinks {
+            let edge = source * num_sinks + sink;
+            if self.sinks[sink].on_path {
+                sink += 1;
+                continue;
+            }
+
+            if !self.sinks[sink].dead && self.costs[edge] != UNREACHABLE && self.costs[edge] + self.sources[source].potential == self.sinks[sink].potential {
+                let pushed = self.push_from_sink(sink, limit);
+                if pushed > 0 {
+                    self.flows[edge] += pushed;
+                    self.sources[source].on_path = false;
+                    return pushed;
+                }
+            }
+
+            if sink == self.sources[source].next_edge {
+                self.sources[source].next_edge += 1;
+            }
+            sink += 1;
+        }
+        self.sources[source].on_path = false;
+        self.sources[source].dead = true;
+        0
+    }
+
+    fn push_from_sink(&mut self, sink: usize, limit: i32) -> i32 {
+        let num_sources = self.sources.len();
+        let num_sinks = self.sinks.len();
+        let demand = &mut self.sinks[sink].remaining;
+        if *demand > 0 {
+            let pushed = limit.min(*demand);
+            *demand -= pushed;
+            return pushed;
+        }
+
+        // Redirect flow already arriving here to somewhere else
+        self.sinks[sink].on_path = true;
+        let mut source = self.sinks[sink].next_edge;
+        while source < num_sources {
+            let edge = source * num_sinks + sink;
+            if self.sources[source].on_path {
+                source += 1;
+                continue;
+            }
+
+            let flow = self.flows[edge];
+            if flow > 0 && !self.sources[source].dead && self.sinks[sink].potential - self.costs[edge] == self.sources[source].potential {
+                let pushed = self.push_from_source(source, limit.min(flow));
+                if pushed > 0 {
+                    self.flows[edge] -= pushed;
+                    self.sinks[sink].on_path = false;
+                    return pushed;
+                }
+            }
+
+            if source == self.sinks[sink].next_edge {
+                self.sinks[sink].next_edge += 1;
+            }
+            source += 1;
+        }
+        self.sinks[sink].on_path = false;
+        self.sinks[sink].dead = true;
+        0
+    }
+}
+
+/// A priority queue for small non-negative integer priorities, popping the lowest first.
+/// Priorities must not go below the last one popped, which is true of distances in Dijkstra.
+#[derive(Default)]
+struct BucketQueue {
+    buckets: Vec<Vec<usize>>,
+    current: usize,
+}
+impl BucketQueue {
+    fn clear(&mut self) {
+        for bucket in self.buckets.iter_mut() {
+            bucket.clear();
+        }
+        self.current = 0;
+    }
+
+    fn push(&mut self, priority: i32, item: usize) {
+        let priority = priority as usize;
+        if priority >= self.buckets.len() {
+            self.buckets.resize_with(priority + 1, Vec::new);
+        }
+        self.buckets[priority].push(item);
+    }
+
+    fn pop(&mut self) -> Option<(i32, usize)> {
+        while self.current < self.buckets.len() {
+            if let Some(item) = self.buckets[self.current].pop() {
+                return Some((self.current as i32, item));
+            }
+            self.current += 1;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use super::*;
+    use crate::inputs::*;
+    use crate::mapgen;
+
+    /// A row of cells where some links are broken, so that cells in different pieces cannot reach each other
+    fn broken_row(num_cells: usize, rng: &mut StdRng) -> Layout {
+        let linked: Vec<bool> = (0..num_cells).map(|_| rng.gen_bool(0.8)).collect(); // linked[i] joins cell i to cell i + 1
+        let cells: Vec<CellLayout> = (0..num_cells).map(|cell| {
+            let mut directions = [None; NUM_DIRECTIONS];
+            if cell + 1 < num_cells && linked[cell] { directions[0] = Some(cell + 1) }
+            if cell > 0 && linked[cell - 1] { directions[3] = Some(cell - 1) }
+            CellLayout::new(None, 0, directions)
+        }).collect();
+        Layout {
+            cells: cells.into_boxed_slice(),
+            bases: [Box::new([0]), Box::new([num_cells - 1])],
+        }
+    }
+
+    /// Successive shortest paths with Bellman-Ford on the residual graph, returning the total flow and cost
+    fn solve_by_bellman_ford(excess: &[i32], paths: &PathMap) -> (i32, i64) {
+        // Nodes: 0 is the super source, 1 is the super sink, then one per cell
+        struct Edge { to: usize, capacity: i32, cost: i32 }
+        let num_nodes = excess.len() + 2;
+        let mut edges: Vec<Edge> = Vec::new();
+        let mut adjacent: Vec<Vec<usize>> = vec![Vec::new(); num_nodes];
+        let mut add_edge = |from: usize, to: usize, capacity: i32, cost: i32| {
+            adjacent[from].push(edges.len());
+            edges.push(Edge { to, capacity, cost });
+            adjacent[to].push(edges.len());
+            edges.push(Edge { to: from, capacity: 0, cost: -cost });
+        };
+        for (cell, &amount) in excess.iter().enumerate() {
+            if amount > 0 { add_edge(0, cell + 2, amount, 0) }
+            if amount < 0 { add_edge(cell + 2, 1, -amount, 0) }
+        }
+        for source in (0..excess.len()).filter(|&cell| excess[cell] > 0) {
+            for sink in (0..excess.len()).filter(|&cell| excess[cell] < 0) {
+                let distance = paths.distance_between(source, sink);
+                if distance != i32::MAX { add_edge(source + 2, sink + 2, i32::MAX, distance) }
+            }
+        }
+
+        let (mut total_flow, mut total_cost) = (0, 0);
+        loop {
+            let mut distances = vec![i64::MAX; num_nodes];
+            let mut previous = vec![None; num_nodes];
+            distances[0] = 0;
+            let mut changed = true;
+            while changed {
+                changed = false;
+                for node in 0..num_nodes {
+                    if distances[node] == i64::MAX { continue }
+                    for &e in adjacent[node].iter() {
+                        let edge = &edges[e];
+                        if edge.capacity > 0 && distances[node] + (edge.cost as i64) < distances[edge.to] {
+                            distances[edge.to] = distances[node] + edge.cost as i64;
+                            previous[edge.to] = Some(e);
+                            changed = true;
+                        }
+                    }
+                }
+            }
+            if distances[1] == i64::MAX { break }
+
+            let mut path = Vec::new();
+            let mut node = 1;
+            while let Some(e) = previous[node] {
+                path.push(e);
+                node = edges[e ^ 1].to;
+            }
+            let pushed = path.iter().map(|&e| edges[e].capacity).min().expect("empty path");
+            for &e in path.iter() {
+                edges[e].capacity -= pushed;
+                edges[e ^ 1].capacity += pushed;
+            }
+            total_flow += pushed;
+            total_cost += pushed as i64 * distances[1];
+        }
+        (total_flow, total_cost)
+    }
+
+    #[test]
+    fn solve_matches_bellman_ford() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let generated: Vec<Layout> = (0..10).map(|seed| mapgen::generate(&mut StdRng::seed_from_u64(seed)).0).collect();
+        for instance in 0..1200 {
+            let layout = if instance % 2 == 0 { generated[rng.gen_range(0..generated.len())].clone() } else { broken_row(rng.gen_range(2..15), &mut rng) };
+            let paths = PathMap::generate(&layout);
+
+            let excess: Vec<i32> = (0..layout.cells.len()).map(|_| if rng.gen_bool(0.3) { rng.gen_range(-10..=10) } else { 0 }).collect();
+            let shipments = solve(&excess, &paths);
+
+            let mut sent = vec![0; excess.len()];
+            for shipment in shipments.iter() {
+                assert_ne!(paths.distance_between(shipment.source, shipment.sink), i32::MAX, "shipped between unreachable cells");
+                sent[shipment.source] += shipment.amount;
+                sent[shipment.sink] -= shipment.amount;
+            }
+            for cell in 0..excess.len() {
+                assert!(sent[cell] * excess[cell] >= 0 && sent[cell].abs() <= excess[cell].abs(), "instance {}: cell {} sent {} of {}", instance, cell, sent[cell], excess[cell]);
+            }
+
+            let total_flow: i32 = shipments.iter().map(|shipment| shipment.amount).sum();
+            let total_cost: i64 = shipments.iter().map(|shipment| shipment.amount as i64 * paths.distance_between(shipment.source, shipment.sink) as i64).sum();
+            assert_eq!((total_flow, total_cost), solve_by_bellman_ford(&excess, &paths), "instance {}: excess {:?}", instance, excess);
+        }
+    }
+}
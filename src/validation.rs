@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+use std::fmt::Display;
+
+use super::inputs::*;
+
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Violation {
+    NeighborOutOfRange { cell: usize, direction: usize, neighbor: usize },
+    SelfNeighbor { cell: usize, direction: usize },
+
+    /// The neighbor does not link back to the cell from the opposite direction
+    AsymmetricNeighbor { cell: usize, direction: usize, neighbor: usize },
+
+    /// The compact neighbor list does not match the directional neighbors
+    InconsistentNeighbors { cell: usize },
+
+    MissingBases { player: usize },
+    BaseOutOfRange { player: usize, base: usize },
+    DuplicateBase { base: usize },
+
+    /// The cell cannot be reached from cell 0
+    Disconnected { cell: usize },
+
+    NegativeResources { cell: usize, amount: i32 },
+    ResourcesWithoutContent { cell: usize, amount: i32 },
+}
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NeighborOutOfRange { cell, direction, neighbor } => write!(f, "cell {} direction {}: neighbor {} out of range", cell, direction, neighbor),
+            Self::SelfNeighbor { cell, direction } => write!(f, "cell {} direction {}: cell is its own neighbor", cell, direction),
+            Self::AsymmetricNeighbor { cell, direction, neighbor } => write!(f, "cell {} direction {}: neighbor {} does not link back", cell, direction, neighbor),
+            Self::InconsistentNeighbors { cell } => write!(f, "cell {}: neighbor list does not match directions", cell),
+            Self::MissingBases { player } => write!(f, "player {}: no bases", player),
+            Self::BaseOutOfRange { player, base } => write!(f, "player {}: base {} out of range", player, base),
+            Self::DuplicateBase { base } => write!(f, "base {}: used more than once", base),
+            Self::Disconnected { cell } => write!(f, "cell {}: unreachable from cell 0", cell),
+            Self::NegativeResources { cell, amount } => write!(f, "cell {}: negative resources {}", cell, amount),
+            Self::ResourcesWithoutContent { cell, amount } => write!(f, "cell {}: {} resources but no content", cell, amount),
+        }
+    }
+}
+
+/// Checks a Layout is internally consistent, returning every violation found rather than stopping at the first
+pub fn validate_layout(layout: &Layout) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    validate_neighbors(layout, &mut violations);
+    validate_bases(layout, &mut violations);
+    validate_connected(layout, &mut violations);
+    validate_resources(layout, &mut violations);
+    violations
+}
+
+fn validate_neighbors(layout: &Layout, violations: &mut Vec<Violation>) {
+    let num_cells = layout.cells.len();
+    for (cell, cell_layout) in layout.cells.iter().enumerate() {
+        for (direction, &neighbor) in cell_layout.directions.iter().enumerate() {
+            let neighbor = match neighbor {
+                Some(neighbor) => neighbor,
+                None => continue,
+            };
+
+            if neighbor >= num_cells {
+                violations.push(Violation::NeighborOutOfRange { cell, direction, neighbor });
+            } else if neighbor == cell {
+                violations.push(Violation::SelfNeighbor { cell, direction });
+            } else {
+                let opposite = (direction + NUM_DIRECTIONS / 2) % NUM_DIRECTIONS;
+                if layout.cells[neighbor].directions[opposite] != Some(cell) {
+                    violations.push(Violation::AsymmetricNeighbor { cell, direction, neighbor });
+                }
+            }
+        }
+
+        if !cell_layout.neighbors.iter().cloned().eq(cell_layout.directions.iter().flatten().cloned()) {
+            violations.push(Violation::InconsistentNeighbors { cell });
+        }
+    }
+}
+
+fn validate_bases(layout: &Layout, violations: &mut Vec<Violation>) {
+    let num_cells = layout.cells.len();
+    let mut seen = Vec::new();
+    for (player, bases) in layout.bases.iter().enumerate() {
+        if bases.is_empty() {
+            violations.push(Violation::MissingBases { player });
+        }
+
+        for &base in bases.iter() {
+            if base >= num_cells {
+                violations.push(Violation::BaseOutOfRange { player, base });
+            } else if seen.contains(&base) {
+                violations.push(Violation::DuplicateBase { base });
+            } else {
+                seen.push(base);
+            }
+        }
+    }
+}
+
+fn validate_connected(layout: &Layout, violations: &mut Vec<Violation>) {
+    let num_cells = layout.cells.len();
+    if num_cells == 0 { return }
+
+    let mut reached = vec![false; num_cells];
+    reached[0] = true;
+
+    let mut queue = VecDeque::new();
+    queue.push_back(0);
+    while let Some(cell) = queue.pop_front() {
+        for &neighbor in layout.cells[cell].neighbors.iter() {
+            if neighbor < num_cells && !reached[neighbor] {
+                reached[neighbor] = true;
+                queue.push_back(neighbor);
+            }
+        }
+    }
+
+    for (cell, &reached) in reached.iter().enumerate() {
+        if !reached {
+            violations.push(Violation::Disconnected { cell });
+        }
+    }
+}
+
+fn validate_resources(layout: &Layout, violations: &mut Vec<Violation>) {
+    for (cell, cell_layout) in layout.cells.iter().enumerate() {
+        let amount = cell_layout.initial_resources;
+        if amount < 0 {
+            violations.push(Violation::NegativeResources { cell, amount });
+        } else if amount > 0 && cell_layout.content.is_none() {
+            violations.push(Violation::ResourcesWithoutContent { cell, amount });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(directions: &[(usize, usize)]) -> CellLayout {
+        let mut neighbors = [None; NUM_DIRECTIONS];
+        for &(direction, neighbor) in directions {
+            neighbors[direction] = Some(neighbor);
+        }
+        CellLayout::new(None, 0, neighbors)
+    }
+
+    #[test]
+    fn every_violation_is_reported_at_once() {
+        // Cell 1 links right to cell 2 but cell 2 does not link back, cell 3 is on its own, and both players share a base
+        let layout = Layout {
+            cells: vec![cell(&[(0, 1)]), cell(&[(0, 2), (3, 0)]), cell(&[]), cell(&[])].into_boxed_slice(),
+            bases: [Box::new([0]), Box::new([0])],
+        };
+        assert_eq!(validate_layout(&layout), vec![
+            Violation::AsymmetricNeighbor { cell: 1, direction: 0, neighbor: 2 },
+            Violation::DuplicateBase { base: 0 },
+            Violation::Disconnected { cell: 3 },
+        ]);
+    }
+}
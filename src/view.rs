@@ -1,5 +1,9 @@
+use std::fmt::Display;
+
+use super::diagnostic;
 use super::inputs::*;
 use super::pathing::*;
+use super::validation;
 
 pub type AntsPerCell = Box<[i32]>;
 pub type AntsPerCellPerPlayer = [AntsPerCell; NUM_PLAYERS];
@@ -18,16 +22,27 @@ pub struct View {
     pub paths: PathMap,
 
     /// player -> cell -> closest base
+    #[allow(dead_code)]
     pub closest_bases: ClosestBasesPerPlayer,
     pub distance_to_closest_base: DistanceToClosestBasePerPlayer,
 
     /// player -> cell containing resources, sorted nearest to farthest
     pub closest_crystals: ClosestResourcesPerPlayer,
+    #[allow(dead_code)]
     pub closest_eggs: ClosestResourcesPerPlayer,
+    #[allow(dead_code)]
     pub closest_resources: ClosestResourcesPerPlayer,
 }
 impl View {
     pub fn new(layout: Layout) -> Self {
+        if cfg!(debug_assertions) {
+            let violations = validation::validate_layout(&layout);
+            for violation in violations.iter() {
+                diagnostic!("Invalid layout: {}", violation);
+            }
+            assert!(violations.is_empty(), "invalid layout: {} violations", violations.len());
+        }
+
         let paths = PathMap::generate(&layout);
 
         let closest_bases = [
@@ -121,31 +136,156 @@ impl State {
     }
 }
 
-pub fn find_winner(view: &View, state: &State) -> Option<usize> {
+/// Why the game ended
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum EndReason {
+    /// A player harvested more than half the crystals, or exactly half so that the other player can no longer win
+    Threshold,
+
+    /// Every crystal cell has been harvested
+    NoCrystals,
+
+    TickLimit,
+
+    /// A player failed to respond correctly. Only the referee ends games this way.
+    #[allow(dead_code)]
+    Forfeit,
+}
+impl Display for EndReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Threshold => write!(f, "crystal threshold"),
+            Self::NoCrystals => write!(f, "no crystals remaining"),
+            Self::TickLimit => write!(f, "tick limit"),
+            Self::Forfeit => write!(f, "forfeit"),
+        }
+    }
+}
+
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Outcome {
+    /// by_ants means crystals were tied and the winner was decided by the number of ants
+    Win { player: usize, reason: EndReason, by_ants: bool },
+    Draw { reason: EndReason },
+}
+#[allow(dead_code)]
+impl Outcome {
+    pub fn winner(&self) -> Option<usize> {
+        match self {
+            &Self::Win { player, .. } => Some(player),
+            Self::Draw { .. } => None,
+        }
+    }
+
+    pub fn reason(&self) -> EndReason {
+        match self {
+            &Self::Win { reason, .. } | &Self::Draw { reason } => reason,
+        }
+    }
+}
+
+/// The outcome of the game if it has ended, otherwise None.
+/// The game ends when a player reaches the crystal threshold, when no crystals remain, or at the tick limit, checked in that order.
+pub fn find_outcome(view: &View, state: &State) -> Option<Outcome> {
     let threshold = view.initial_crystals / 2;
-    
-    let mut is_game_over = state.tick >= MAX_TICKS;
+
+    let mut reason = None;
     for player in 0..NUM_PLAYERS {
         if state.crystals[player] > threshold {
-            return Some(player);
+            return Some(Outcome::Win { player, reason: EndReason::Threshold, by_ants: false });
         } else if state.crystals[player] == threshold {
-            is_game_over = true;
+            reason = Some(EndReason::Threshold);
         }
     }
+    if reason.is_none() && view.closest_crystals[ME].iter().all(|&cell| state.resources[cell] <= 0) {
+        reason = Some(EndReason::NoCrystals);
+    }
+    if reason.is_none() && state.tick >= MAX_TICKS {
+        reason = Some(EndReason::TickLimit);
+    }
+    let reason = reason?;
+
+    let outcome = if state.crystals[ME] != state.crystals[ENEMY] {
+        let player = if state.crystals[ME] > state.crystals[ENEMY] { ME } else { ENEMY };
+        Outcome::Win { player, reason, by_ants: false }
+    } else if state.total_ants[ME] != state.total_ants[ENEMY] {
+        let player = if state.total_ants[ME] > state.total_ants[ENEMY] { ME } else { ENEMY };
+        Outcome::Win { player, reason, by_ants: true }
+    } else {
+        Outcome::Draw { reason }
+    };
+    Some(outcome)
+}
 
-    if is_game_over {
-        if state.crystals[ME] > state.crystals[ENEMY] {
-            return Some(ME);
-        } else if state.crystals[ME] < state.crystals[ENEMY] {
-            return Some(ENEMY);
-        } else if state.total_ants[ME] > state.total_ants[ENEMY] {
-            return Some(ME);
-        } else if state.total_ants[ME] < state.total_ants[ENEMY] {
-            return Some(ENEMY);
-        } else {
-            return Some(ENEMY);
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use super::*;
+    use crate::mapgen;
+
+    fn generate() -> (View,State) {
+        let (layout, state) = mapgen::generate(&mut StdRng::seed_from_u64(0));
+        (View::new(layout), state)
+    }
+
+    fn with(state: &State, tick: u32, crystals: CrystalsPerPlayer, extra_ants: [i32; NUM_PLAYERS], harvest_all: bool, view: &View) -> State {
+        let mut num_ants = state.num_ants.clone();
+        for player in 0..NUM_PLAYERS {
+            num_ants[player][view.layout.bases[player][0]] += extra_ants[player];
+        }
+
+        let mut resources = state.resources.clone();
+        if harvest_all {
+            for &cell in view.closest_crystals[ME].iter() {
+                resources[cell] = 0;
+            }
         }
+
+        State::new(tick, num_ants, resources, crystals)
     }
 
-    None
-}
\ No newline at end of file
+    #[test]
+    fn game_continues_before_any_end_condition() {
+        let (view, state) = generate();
+        assert_eq!(find_outcome(&view, &with(&state, 10, [1, 0], [0, 0], false, &view)), None);
+    }
+
+    #[test]
+    fn no_crystals_ends_game() {
+        let (view, state) = generate();
+        assert_eq!(
+            find_outcome(&view, &with(&state, 10, [1, 2], [0, 0], true, &view)),
+            Some(Outcome::Win { player: ENEMY, reason: EndReason::NoCrystals, by_ants: false }));
+    }
+
+    #[test]
+    fn crystal_tie_is_broken_on_ants() {
+        let (view, state) = generate();
+        assert_eq!(
+            find_outcome(&view, &with(&state, 10, [3, 3], [5, 0], true, &view)),
+            Some(Outcome::Win { player: ME, reason: EndReason::NoCrystals, by_ants: true }));
+    }
+
+    #[test]
+    fn full_tie_is_a_draw() {
+        let (view, state) = generate();
+        assert_eq!(
+            find_outcome(&view, &with(&state, MAX_TICKS, [3, 3], [0, 0], false, &view)),
+            Some(Outcome::Draw { reason: EndReason::TickLimit }));
+    }
+
+    #[test]
+    fn threshold_takes_priority() {
+        let (view, state) = generate();
+        let threshold = view.initial_crystals / 2;
+        assert_eq!(
+            find_outcome(&view, &with(&state, MAX_TICKS, [0, threshold + 1], [0, 0], true, &view)),
+            Some(Outcome::Win { player: ENEMY, reason: EndReason::Threshold, by_ants: false }));
+
+        // Exactly half is still the threshold, decided by the crystals so far
+        assert_eq!(
+            find_outcome(&view, &with(&state, MAX_TICKS, [threshold, threshold - 1], [0, 0], true, &view)),
+            Some(Outcome::Win { player: ME, reason: EndReason::Threshold, by_ants: false }));
+    }
+}
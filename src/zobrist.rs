@@ -0,0 +1,118 @@
+//! A deterministic 64-bit hash of a State, in the spirit of Zobrist hashing.
+//! Every feature (ants of a player on a cell, resources on a cell, crystals of a player, the tick) has a fixed pseudo-random key,
+//! and the hash is the sum of each key multiplied by the feature's value.
+//! Summing rather than XORing means a change can be applied knowing only how much a value changed, not what it was before,
+//! so the hash can follow the simulation's event log without looking at the State.
+
+use super::inputs::*;
+use super::simulation::{Event,EventLog};
+use super::view::*;
+
+const ANTS_KEYS: u64 = 0;
+const RESOURCES_KEYS: u64 = ANTS_KEYS + NUM_PLAYERS as u64;
+const CRYSTALS_KEYS: u64 = RESOURCES_KEYS + 1;
+const TICK_KEY: u64 = CRYSTALS_KEYS + 1;
+
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub struct StateHash(pub u64);
+impl StateHash {
+    pub fn of(state: &State) -> Self {
+        let mut hash = Self(0);
+        hash.add(key(TICK_KEY, 0), state.tick as i32);
+        for player in 0..NUM_PLAYERS {
+            hash.add(key(CRYSTALS_KEYS, player), state.crystals[player]);
+            for (cell, &num_ants) in state.num_ants[player].iter().enumerate() {
+                hash.add(key(ANTS_KEYS + player as u64, cell), num_ants);
+            }
+        }
+        for (cell, &resources) in state.resources.iter().enumerate() {
+            hash.add(key(RESOURCES_KEYS, cell), resources);
+        }
+        hash
+    }
+
+    /// Updates the hash for one event from simulation::forward_with_log
+    pub fn apply(&mut self, event: &Event) {
+        match *event {
+            Event::Ticked { .. } => self.add(key(TICK_KEY, 0), 1),
+            Event::Moved { player, source, target, num_ants } => {
+                self.add(key(ANTS_KEYS + player as u64, source), -num_ants);
+                self.add(key(ANTS_KEYS + player as u64, target), num_ants);
+            },
+            Event::Contested { .. } => (),
+            Event::Harvested { player, content: Content::Crystals, amount, .. } => self.add(key(CRYSTALS_KEYS, player), amount),
+            Event::Harvested { content: Content::Eggs, .. } => (), // Counted by the Spawned events
+            Event::Depleted { cell, amount } => self.add(key(RESOURCES_KEYS, cell), -amount),
+            Event::Spawned { player, base, num_ants } => self.add(key(ANTS_KEYS + player as u64, base), num_ants),
+        }
+    }
+
+    fn add(&mut self, key: u64, value: i32) {
+        self.0 = self.0.wrapping_add(key.wrapping_mul(value as i64 as u64));
+    }
+}
+impl EventLog for StateHash {
+    fn log(&mut self, event: Event) {
+        self.apply(&event);
+    }
+}
+
+/// A fixed pseudo-random key for the feature, using the SplitMix64 finalizer so no key table needs to be stored
+fn key(kind: u64, index: usize) -> u64 {
+    let mut z = ((kind << 32) | index as u64).wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use super::*;
+    use crate::mapgen;
+    use crate::movement::{self,MovementModel};
+    use crate::simulation;
+
+    #[test]
+    fn incremental_hash_matches_recalculation() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for seed in 0..30 {
+            let (layout, initial) = mapgen::generate(&mut StdRng::seed_from_u64(seed));
+            let view = View::new(layout);
+            for model in [MovementModel::MinCost, MovementModel::Referee] {
+                let mut state = initial.clone();
+                let mut hash = StateHash::of(&state);
+                for _ in 0..60 {
+                    let assignments = [ME, ENEMY].map(|player| {
+                        let beacons: Vec<(usize,i32)> = (0..4).map(|_| (rng.gen_range(0..view.layout.cells.len()), rng.gen_range(1..5))).collect();
+                        movement::spread_ants_across_beacons(beacons.into_iter(), player, &view, &state)
+                    });
+                    simulation::forward_with_log(&assignments, model, &view, &mut state, &mut hash);
+                    assert_eq!(hash, StateHash::of(&state), "map {}, {:?}, tick {}", seed, model, state.tick);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_feature_changes_the_hash() {
+        let (_, state) = mapgen::generate(&mut StdRng::seed_from_u64(0));
+        let hash = StateHash::of(&state);
+
+        let mut ants = state.clone();
+        ants.num_ants[ENEMY][0] += 1;
+        let mut resources = state.clone();
+        resources.resources[0] += 1;
+        let mut crystals = state.clone();
+        crystals.crystals[ME] += 1;
+        let mut tick = state.clone();
+        tick.tick += 1;
+
+        let changed = [ants, resources, crystals, tick].map(|state| StateHash::of(&state));
+        for (i, &other) in changed.iter().enumerate() {
+            assert_ne!(other, hash);
+            assert!(changed[(i + 1)..].iter().all(|&later| later != other));
+        }
+    }
+}